@@ -4,6 +4,11 @@ fn main() {
     let target = env::var("TARGET").unwrap();
     let out_dir = env::var("OUT_DIR").unwrap();
 
+    // The native registry replaces the C++ one entirely.
+    if env::var_os("CARGO_FEATURE_NATIVE_REGISTRY").is_some() {
+        return;
+    }
+
     println!("cargo:rerun-if-changed=src_c/");
     let mut config = cc::Build::new();
     config.file("src_c/th.cc");
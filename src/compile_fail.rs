@@ -0,0 +1,87 @@
+//! Compile-fail checks for the auto traits of `Frc`, `FrcWeak` and `Singleton`,
+//! for `frc_unsize!` and for invalid weight policies.
+//!
+//! A value that is not `Sync` must not be reachable from two threads:
+//!
+//! ```compile_fail
+//! let value = frc::Frc::new(std::cell::Cell::new(0_i32));
+//! let remote = value.clone();
+//! std::thread::spawn(move || remote.set(1));
+//! ```
+//!
+//! ```compile_fail
+//! let value = frc::Frc::new(std::cell::Cell::new(0_i32));
+//! let weak = frc::Frc::downgrade(&value);
+//! std::thread::spawn(move || drop(weak));
+//! ```
+//!
+//! Neither may a value that is not `Send`, since the last clone drops it on
+//! whichever thread it lives:
+//!
+//! ```compile_fail
+//! let value = frc::Frc::new(std::rc::Rc::new(0_i32));
+//! let remote = value.clone();
+//! std::thread::spawn(move || drop(remote));
+//! ```
+//!
+//! ```compile_fail
+//! fn assert_sync<T: Sync>() {}
+//! assert_sync::<frc::Frc<std::rc::Rc<i32>>>();
+//! ```
+//!
+//! ```compile_fail
+//! static SHARED: frc::Singleton<std::cell::Cell<i32>> = frc::Singleton::default();
+//! ```
+//!
+//! Values that are `Send + Sync` still cross threads:
+//!
+//! ```
+//! let value = frc::Frc::new(std::sync::Mutex::new(0_i32));
+//! let remote = value.clone();
+//! std::thread::spawn(move || *remote.lock().unwrap() += 1)
+//!     .join()
+//!     .unwrap();
+//! assert_eq!(*value.lock().unwrap(), 1);
+//! ```
+//!
+//! `frc_unsize!` only accepts unsizing coercions, not a field of the value:
+//!
+//! ```compile_fail
+//! struct Loud(String);
+//! let loud = frc::Frc::new(Loud(String::from("frc")));
+//! let field: frc::Frc<String> = frc::frc_unsize!(loud, String);
+//! ```
+//!
+//! ```
+//! let shown = frc::frc_unsize!(frc::Frc::new(42_u32), dyn std::fmt::Display);
+//! assert_eq!(shown.to_string(), "42");
+//! ```
+//!
+//! A policy giving handles no weight is rejected by every constructor, the
+//! slice ones included:
+//!
+//! ```compile_fail
+//! struct Zero;
+//! impl frc::WeightPolicy for Zero {
+//!     const INITIAL_WEIGHT: usize = 0;
+//! }
+//! let values: frc::Frc<[String], Zero> = frc::Frc::from(vec![String::from("frc")]);
+//! ```
+//!
+//! ```compile_fail
+//! struct Zero;
+//! impl frc::WeightPolicy for Zero {
+//!     const INITIAL_WEIGHT: usize = 0;
+//! }
+//! let text: frc::Frc<str, Zero> = frc::Frc::from("frc");
+//! ```
+//!
+//! ```
+//! struct Small;
+//! impl frc::WeightPolicy for Small {
+//!     const INITIAL_WEIGHT: usize = 4;
+//! }
+//! let values: frc::Frc<[String], Small> = frc::Frc::from(vec![String::from("frc")]);
+//! let weak = frc::Frc::downgrade(&values);
+//! assert_eq!(weak.upgrade().unwrap()[0], "frc");
+//! ```
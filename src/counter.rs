@@ -0,0 +1,51 @@
+use crate::local_vec::ThreadLocalVec;
+use crate::sync::{
+    atomic::{self, AtomicU64},
+    const_unless_loom,
+};
+use std::fmt;
+
+/// A counter sharded by thread number.
+///
+/// Every thread adds to its own shard, so `add` never contends with other
+/// threads. `sum` adds up the shards without locking; it is exact once the
+/// adding threads are done, and a snapshot otherwise.
+#[derive(Default)]
+pub struct Counter {
+    shards: ThreadLocalVec<AtomicU64>,
+}
+
+impl Counter {
+    const_unless_loom! {
+        pub fn new() -> Counter {
+            Counter {
+                shards: ThreadLocalVec::new(),
+            }
+        }
+    }
+
+    #[inline]
+    pub fn add(&self, n: u64) {
+        self.shards
+            .get_or(|| AtomicU64::new(0))
+            .fetch_add(n, atomic::Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    pub fn sum(&self) -> u64 {
+        self.shards
+            .iter()
+            .map(|shard| shard.load(atomic::Ordering::Relaxed))
+            .sum()
+    }
+}
+
+impl fmt::Debug for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Counter").field(&self.sum()).finish()
+    }
+}
@@ -0,0 +1,331 @@
+//! Epoch-based reclamation.
+//!
+//! A thread reading shared pointers pins itself with `pin()`. Memory
+//! unlinked while some thread is pinned is handed to `Guard::defer` or
+//! `Guard::defer_destroy`, and freed once every thread pinned at that time
+//! has unpinned.
+//!
+//! Every thread number has an epoch slot. Only registered threads are
+//! waited for: a thread unpins before its number is released, so the slot of
+//! an unregistered thread never holds the epoch back. Unregistering a pinned
+//! thread takes effect when its last guard is dropped.
+use crate::local_vec::ThreadLocalVec;
+use crate::registry::{Backend, Registry, UNREGISTERED};
+use crate::sync::{
+    atomic::{self, AtomicUsize},
+    const_unless_loom,
+};
+use std::{cell::Cell, fmt, marker::PhantomData, mem, sync::Mutex};
+
+// Pins between two attempts by a thread to free deferred functions.
+const PINS_BETWEEN_COLLECT: usize = 128;
+// Deferred functions queued before `defer` tries to free some.
+const GARBAGE_BEFORE_COLLECT: usize = 64;
+
+// The epoch slot of a thread number: the pinned epoch shifted left by one,
+// with the low bit set while pinned, or 0 while unpinned.
+#[derive(Default)]
+struct Local {
+    state: AtomicUsize,
+}
+
+struct Deferred {
+    epoch: usize,
+    call: Box<dyn FnOnce() + Send>,
+}
+
+struct Global {
+    epoch: AtomicUsize,
+    locals: ThreadLocalVec<Local>,
+    // Never locked around loom operations, so loom need not see it.
+    garbage: Mutex<Vec<Deferred>>,
+}
+
+impl Global {
+    const_unless_loom! {
+        fn new() -> Global {
+            Global {
+                epoch: AtomicUsize::new(0),
+                locals: ThreadLocalVec::new(),
+                garbage: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    // Moves the global epoch forward if every pinned thread has seen it.
+    fn try_advance(&self) -> usize {
+        let epoch = self.epoch.load(atomic::Ordering::Relaxed);
+        atomic::fence(atomic::Ordering::SeqCst);
+        for local in self.locals.iter() {
+            let state = local.state.load(atomic::Ordering::Relaxed);
+            if state & 1 == 1 && state >> 1 != epoch {
+                return epoch;
+            }
+        }
+        atomic::fence(atomic::Ordering::Acquire);
+        match self.epoch.compare_exchange(
+            epoch,
+            epoch + 1,
+            atomic::Ordering::Release,
+            atomic::Ordering::Relaxed,
+        ) {
+            Ok(_) => epoch + 1,
+            Err(current) => current,
+        }
+    }
+
+    // Runs the deferred functions no pinned thread can still be using.
+    fn collect(&self) {
+        let epoch = self.try_advance();
+        let ready = {
+            let mut garbage = self.garbage.lock().unwrap_or_else(|e| e.into_inner());
+            let (ready, waiting) = mem::take(&mut *garbage)
+                .into_iter()
+                .partition::<Vec<_>, _>(|deferred| deferred.epoch + 2 <= epoch);
+            *garbage = waiting;
+            ready
+        };
+        // A deferred function that panics leaves the ones after it queued.
+        let mut ready = Requeue {
+            global: self,
+            rest: ready.into_iter(),
+        };
+        for deferred in ready.rest.by_ref() {
+            (deferred.call)();
+        }
+    }
+}
+
+// Puts the deferred functions left by an unwinding `collect` back.
+struct Requeue<'a> {
+    global: &'a Global,
+    rest: std::vec::IntoIter<Deferred>,
+}
+
+impl Drop for Requeue<'_> {
+    fn drop(&mut self) {
+        if self.rest.len() == 0 {
+            return;
+        }
+        self.global
+            .garbage
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(self.rest.by_ref());
+    }
+}
+
+#[cfg(not(loom))]
+static GLOBAL: Global = Global::new();
+
+#[cfg(not(loom))]
+fn global() -> &'static Global {
+    &GLOBAL
+}
+
+// Loom resets its statics for every execution.
+#[cfg(loom)]
+loom::lazy_static! {
+    static ref GLOBAL: Global = Global::new();
+}
+
+#[cfg(loom)]
+fn global() -> &'static Global {
+    &GLOBAL
+}
+
+// The pinning state of the current thread.
+struct Handle {
+    depth: Cell<usize>,
+    local: Cell<*const Local>,
+    pins: Cell<usize>,
+    // Set when the thread was unregistered while pinned.
+    unregister: Cell<bool>,
+}
+
+impl Handle {
+    const fn new() -> Handle {
+        Handle {
+            depth: Cell::new(0),
+            local: Cell::new(std::ptr::null()),
+            pins: Cell::new(0),
+            unregister: Cell::new(false),
+        }
+    }
+}
+
+#[cfg(not(loom))]
+std::thread_local! {
+    static HANDLE: Handle = const { Handle::new() };
+}
+
+// Loom model threads share an OS thread.
+#[cfg(loom)]
+loom::thread_local! {
+    static HANDLE: Handle = Handle::new();
+}
+
+/// Keeps the current thread pinned. Memory deferred while it is alive is
+/// not freed until it is dropped.
+#[must_use = "the thread is unpinned again when the guard is dropped"]
+pub struct Guard {
+    // Pinning belongs to the thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+/// Pins the current thread, registering it if it is not registered yet.
+///
+/// Guards nest: the thread stays pinned until the last one is dropped.
+///
+/// # Panics
+///
+/// Panics when called by a thread whose thread locals are being destroyed.
+pub fn pin() -> Guard {
+    try_pin().unwrap_or_else(|| {
+        panic!("epoch::pin called while the thread locals of its thread are being destroyed")
+    })
+}
+
+/// Pins the current thread like `pin`, or returns `None` when called by a
+/// thread whose thread locals are being destroyed.
+pub fn try_pin() -> Option<Guard> {
+    let collect = HANDLE
+        .try_with(|handle| {
+            let depth = handle.depth.get();
+            if depth > 0 {
+                handle.depth.set(depth + 1);
+                return Some(false);
+            }
+            let global = global();
+            let no = crate::registry::current_thread().number();
+            if no == UNREGISTERED {
+                return None;
+            }
+            handle.depth.set(1);
+            let local = global.locals.get_or(Local::default);
+            let epoch = global.epoch.load(atomic::Ordering::Relaxed);
+            local.state.store(epoch << 1 | 1, atomic::Ordering::Relaxed);
+            // Orders the slot before every read made under the guard, and
+            // pairs with the fence in `try_advance`.
+            atomic::fence(atomic::Ordering::SeqCst);
+            handle.local.set(local);
+
+            let pins = handle.pins.get().wrapping_add(1);
+            handle.pins.set(pins);
+            Some(pins % PINS_BETWEEN_COLLECT == 0)
+        })
+        .ok()
+        .flatten()?;
+    let guard = Guard {
+        _not_send: PhantomData,
+    };
+    // Deferred functions may panic, and the guard then unpins the thread as
+    // it unwinds.
+    if collect {
+        global().collect();
+    }
+    Some(guard)
+}
+
+/// Whether the current thread is pinned.
+pub fn is_pinned() -> bool {
+    HANDLE
+        .try_with(|handle| handle.depth.get() > 0)
+        .unwrap_or(false)
+}
+
+impl Guard {
+    /// Calls `f` once no thread pinned now is pinned anymore.
+    pub fn defer<F: FnOnce() + Send + 'static>(&self, f: F) {
+        defer_unpinned(f);
+    }
+
+    /// Drops the `Box<T>` behind `ptr` once no thread pinned now is pinned
+    /// anymore.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from `Box::into_raw`, and must not be reachable by
+    /// threads pinning after this call.
+    pub unsafe fn defer_destroy<T: Send + 'static>(&self, ptr: *mut T) {
+        struct SendPtr<T>(*mut T);
+        unsafe impl<T: Send> Send for SendPtr<T> {}
+
+        let ptr = SendPtr(ptr);
+        self.defer(move || {
+            let ptr = ptr;
+            drop(Box::from_raw(ptr.0));
+        });
+    }
+
+    /// Advances the epoch if possible, and runs the deferred functions that
+    /// became safe to run.
+    pub fn flush(&self) {
+        global().collect();
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let _ = HANDLE.try_with(|handle| {
+            let depth = handle.depth.get() - 1;
+            handle.depth.set(depth);
+            if depth > 0 {
+                return;
+            }
+            let local = handle.local.replace(std::ptr::null());
+            unsafe { (*local).state.store(0, atomic::Ordering::Release) };
+            if handle.unregister.replace(false) {
+                crate::registry::unregister();
+            }
+        });
+    }
+}
+
+impl fmt::Debug for Guard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Guard").finish_non_exhaustive()
+    }
+}
+
+// Called by the registry before releasing the number of the current thread.
+// A pinned thread keeps its number until its last guard is dropped.
+pub(crate) fn defer_unregister() -> bool {
+    HANDLE
+        .try_with(|handle| {
+            let pinned = handle.depth.get() > 0 && Backend::is_thread_registered();
+            if pinned {
+                handle.unregister.set(true);
+            }
+            pinned
+        })
+        .unwrap_or(false)
+}
+
+// Cancels an unregistration waiting for the thread to unpin, for a guard
+// registering the thread again. Returns whether there was one.
+pub(crate) fn take_deferred_unregister() -> bool {
+    HANDLE
+        .try_with(|handle| handle.unregister.replace(false))
+        .unwrap_or(false)
+}
+
+// `Guard::defer` for threads that may not be able to pin. The tag is read
+// after the fence, so a thread pinned before `f` was queued is either at
+// that epoch or one behind, and holds the epoch back until it unpins.
+pub(crate) fn defer_unpinned<F: FnOnce() + Send + 'static>(f: F) {
+    let global = global();
+    atomic::fence(atomic::Ordering::SeqCst);
+    let epoch = global.epoch.load(atomic::Ordering::Relaxed);
+    let queued = {
+        let mut garbage = global.garbage.lock().unwrap_or_else(|e| e.into_inner());
+        garbage.push(Deferred {
+            epoch,
+            call: Box::new(f),
+        });
+        garbage.len()
+    };
+    if queued >= GARBAGE_BEFORE_COLLECT {
+        global.collect();
+    }
+}
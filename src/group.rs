@@ -0,0 +1,171 @@
+use crate::sync::{
+    atomic::{self, AtomicU64},
+    const_unless_loom, Mutex, MutexGuard,
+};
+use crate::Singleton;
+use std::fmt;
+
+/// Makes stores into several singletons visible together.
+///
+/// Singletons join a group with `Singleton::in_group`. Every store into a
+/// member, and every transaction committed on the group, runs as one write
+/// of the group, and `snapshot` retries its reads until no write ran in
+/// between. A snapshot therefore never mixes values from before and after a
+/// transaction. Plain `get` calls are not covered: they can still see a
+/// transaction half committed.
+///
+/// The group is a sequence lock: the sequence is odd while a write runs, and
+/// bumped to the next even number when it ends.
+pub struct SingletonGroup {
+    sequence: AtomicU64,
+    // Held by the running write, and waited on by snapshots meeting it.
+    writer: Mutex<()>,
+}
+
+impl SingletonGroup {
+    const_unless_loom! {
+        pub fn new() -> SingletonGroup {
+            SingletonGroup {
+                sequence: AtomicU64::new(0),
+                writer: Mutex::new(()),
+            }
+        }
+    }
+
+    /// Starts a transaction. Its updates are staged, and stored together by
+    /// `SingletonTransaction::commit`.
+    pub fn transaction(&self) -> SingletonTransaction<'_> {
+        SingletonTransaction {
+            group: self,
+            staged: Vec::new(),
+        }
+    }
+
+    /// Calls `f` until it runs while no write of the group does, and returns
+    /// what it returned then. `f` reads the members of the group, for
+    /// instance `|| (ROUTES.get(), POOL.get())`, and may run several times.
+    pub fn snapshot<R>(&self, mut f: impl FnMut() -> R) -> R {
+        loop {
+            let before = self.sequence.load(atomic::Ordering::Acquire);
+            if before % 2 == 1 {
+                // Blocks until the running write ends.
+                drop(self.writer.lock().unwrap_or_else(|e| e.into_inner()));
+                continue;
+            }
+            let result = f();
+            // Orders the reads of `f` before checking the sequence again.
+            atomic::fence(atomic::Ordering::Acquire);
+            if self.sequence.load(atomic::Ordering::Relaxed) == before {
+                return result;
+            }
+        }
+    }
+
+    // Starts a write, waiting for the running one to end.
+    pub(crate) fn write(&self) -> GroupWrite<'_> {
+        let writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        self.sequence.fetch_add(1, atomic::Ordering::Relaxed);
+        // Orders the odd sequence before the stores of the write, so a
+        // snapshot seeing one of them sees the write running.
+        atomic::fence(atomic::Ordering::Release);
+        GroupWrite {
+            group: self,
+            _writer: writer,
+        }
+    }
+}
+
+impl Default for SingletonGroup {
+    fn default() -> Self {
+        SingletonGroup::new()
+    }
+}
+
+impl fmt::Debug for SingletonGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SingletonGroup")
+            .field("sequence", &self.sequence.load(atomic::Ordering::Relaxed))
+            .finish()
+    }
+}
+
+// A running write of a group, ended when dropped. The sequence is bumped
+// before the lock is released, so snapshots woken by it see the write done.
+pub(crate) struct GroupWrite<'a> {
+    group: &'a SingletonGroup,
+    _writer: MutexGuard<'a, ()>,
+}
+
+impl Drop for GroupWrite<'_> {
+    fn drop(&mut self) {
+        self.group.sequence.fetch_add(1, atomic::Ordering::Release);
+    }
+}
+
+/// Updates to members of a `SingletonGroup`, staged until `commit`.
+///
+/// Dropping a transaction without committing it discards its updates.
+#[must_use = "a transaction does nothing unless committed"]
+pub struct SingletonTransaction<'a> {
+    group: &'a SingletonGroup,
+    staged: Vec<Box<dyn FnOnce() + 'a>>,
+}
+
+impl<'a> SingletonTransaction<'a> {
+    /// Stages storing `data` in `singleton`, like `Singleton::replace`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `singleton` is not a member of the group.
+    pub fn replace<T: Send + Sync + 'static>(
+        &mut self,
+        singleton: &'a Singleton<T>,
+        data: T,
+    ) -> &mut Self {
+        self.stage(singleton, Some(data))
+    }
+
+    /// Stages emptying `singleton`, like `Singleton::take`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `singleton` is not a member of the group.
+    pub fn take<T: Send + Sync + 'static>(&mut self, singleton: &'a Singleton<T>) -> &mut Self {
+        self.stage(singleton, None)
+    }
+
+    /// Stores the staged updates in the order they were staged, as one write
+    /// of the group.
+    ///
+    /// An update that panics ends the write all the same, leaving the
+    /// updates before it stored.
+    pub fn commit(self) {
+        let _write = self.group.write();
+        for update in self.staged {
+            update();
+        }
+    }
+
+    fn stage<T: Send + Sync + 'static>(
+        &mut self,
+        singleton: &'a Singleton<T>,
+        data: Option<T>,
+    ) -> &mut Self {
+        assert!(
+            singleton.is_in_group(self.group),
+            "SingletonTransaction updated a singleton outside its group"
+        );
+        self.staged.push(Box::new(move || {
+            drop(singleton.swap_in_group(data));
+        }));
+        self
+    }
+}
+
+impl fmt::Debug for SingletonTransaction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SingletonTransaction")
+            .field("staged", &self.staged.len())
+            .finish_non_exhaustive()
+    }
+}
@@ -1,95 +1,563 @@
-use crate::inner::Inner;
+use crate::inner::{inner_layout, Inner};
+use crate::policy::{DefaultWeight, PolicyMarker, WeightPolicy};
+use crate::registry::ThreadToken;
+use crate::sync::atomic;
+use crate::weak::FrcWeak;
 use std::{
+    alloc::Layout,
+    any::Any,
     cell::Cell,
     clone::Clone,
     cmp::Ordering,
+    convert::Infallible,
     fmt,
     hash::{Hash, Hasher},
+    iter::FromIterator,
+    mem::{self, MaybeUninit},
     ops::{Deref, DerefMut},
-    ptr::NonNull,
+    ptr::{self, NonNull},
 };
 
-pub const DEFAULT_WEIGHT: usize = 1 << 16;
-const DEFAULT_ADD_WEIGHT: usize = DEFAULT_WEIGHT << 1;
-
-pub struct Frc<T: ?Sized> {
+pub struct Frc<T: ?Sized, P: WeightPolicy = DefaultWeight> {
     pub(crate) weight: Cell<usize>,
     pub(crate) ptr: NonNull<Inner<T>>,
-    pub(crate) thread_no: u32,
+    pub(crate) thread: ThreadToken,
+    pub(crate) _policy: PolicyMarker<P>,
 }
 
 impl<T> Frc<T> {
     #[inline]
     pub fn new(data: T) -> Frc<T> {
+        Frc::with_policy(data, DefaultWeight)
+    }
+
+    /// Constructs a new `Frc<T>` while giving you a `FrcWeak<T>` to the allocation,
+    /// to allow you to construct a `T` which holds a weak pointer to itself.
+    ///
+    /// Calling `upgrade` on the weak reference inside `data_fn` returns `None`.
+    pub fn new_cyclic<F>(data_fn: F) -> Frc<T>
+    where
+        F: FnOnce(&FrcWeak<T>) -> T,
+    {
+        // Start with no strong weight so that upgrades fail until the data is
+        // written, and hand the closure a weak handle with the default weight.
+        let uninit = Box::new(Inner::new(MaybeUninit::<T>::uninit(), 0));
+        uninit.add_weak_weight(DefaultWeight::INITIAL_WEIGHT);
+        let ptr = NonNull::new(Box::into_raw(uninit) as *mut Inner<T>).unwrap();
+        let thread = crate::registry::current_thread();
+        let weak = FrcWeak {
+            weight: Cell::new(DefaultWeight::INITIAL_WEIGHT),
+            ptr,
+            thread,
+            _policy: PolicyMarker::new(),
+        };
+
+        let unit = ReleaseUnit(ptr);
+        let data = data_fn(&weak);
+        mem::forget(unit);
+        unsafe {
+            ptr::write(ptr::addr_of_mut!((*ptr.as_ptr()).data), data);
+            // Publishes the data to weak handles upgrading on other threads.
+            ptr.as_ref().publish_weight(DefaultWeight::INITIAL_WEIGHT);
+        }
+        drop(weak);
+
+        Frc {
+            weight: Cell::new(DefaultWeight::INITIAL_WEIGHT),
+            ptr,
+            thread,
+            _policy: PolicyMarker::new(),
+        }
+    }
+}
+
+// Gives up the unit the strong handles hold on the weak counter when the
+// closure of `new_cyclic` unwinds, so that the last weak handle frees the
+// allocation. Dropped before the weak handle given to the closure.
+struct ReleaseUnit<T: ?Sized>(NonNull<Inner<T>>);
+
+impl<T: ?Sized> Drop for ReleaseUnit<T> {
+    fn drop(&mut self) {
+        unsafe { self.0.as_ref() }.drop_weak_weight(1);
+    }
+}
+
+impl<T, P: WeightPolicy> Frc<T, P> {
+    /// Creates an `Frc` whose handles distribute weight according to `P`.
+    #[inline]
+    pub fn with_policy(data: T, _policy: P) -> Frc<T, P> {
+        Self::new_in(data)
+    }
+
+    #[inline]
+    fn new_in(data: T) -> Frc<T, P> {
         // Allocate the ptr on the heap and set the weights of the values
-        // to the default.
-        let ptr = Box::new(Inner::new(data, DEFAULT_WEIGHT));
-        let thread_no = unsafe { *crate::get_thread_number() };
+        // to the initial weight of the policy.
+        let ptr = Box::new(Inner::new(data, P::INITIAL_WEIGHT));
+        let thread = crate::registry::current_thread();
         Frc {
-            weight: Cell::new(DEFAULT_WEIGHT),
+            weight: Cell::new(P::INITIAL_WEIGHT),
             ptr: NonNull::new(Box::into_raw(ptr)).unwrap(),
-            thread_no,
+            thread,
+            _policy: PolicyMarker::new(),
         }
     }
 
+    /// Returns the inner value if this is the only `Frc` to it, otherwise the
+    /// `Frc` is handed back.
+    pub fn try_unwrap(this: Self) -> Result<T, Self> {
+        if !this.inner().drop_weight_if_unique(this.weight.get()) {
+            return Err(this);
+        }
+        let this = mem::ManuallyDrop::new(this);
+        let data = unsafe { ptr::read(&this.inner().data) };
+        Self::release_weak(&this);
+        Ok(data)
+    }
+
+    /// Drops this `Frc` and returns the inner value if it was the last one.
+    ///
+    /// Unlike `try_unwrap`, exactly one of several handles dropped at the same
+    /// time from different threads gets the value.
+    pub fn into_inner(this: Self) -> Option<T> {
+        let this = mem::ManuallyDrop::new(this);
+        if this.inner().drop_weight(this.weight.get()) > 0 {
+            return None;
+        }
+        atomic::fence(atomic::Ordering::Acquire);
+        let data = unsafe { ptr::read(&this.inner().data) };
+        Self::release_weak(&this);
+        Some(data)
+    }
+}
+
+impl<T: ?Sized, P: WeightPolicy> Frc<T, P> {
+    // Wraps a freshly allocated `Inner` that already carries the initial
+    // weight of the policy.
+    #[inline]
+    unsafe fn from_inner(ptr: *mut Inner<T>) -> Frc<T, P> {
+        Frc {
+            weight: Cell::new(P::INITIAL_WEIGHT),
+            ptr: NonNull::new_unchecked(ptr),
+            thread: crate::registry::current_thread(),
+            _policy: PolicyMarker::new(),
+        }
+    }
+
+    /// Converts an `Frc<T>` into an `Frc<U>`, typically a trait object, keeping
+    /// the weight and thread of the handle. `frc_unsize!` calls it safely.
+    ///
+    /// Unsizing coercions are unstable on `Frc` itself, so `coerce` performs
+    /// it on a pointer to the value instead.
+    ///
+    /// # Safety
+    ///
+    /// `coerce` must return the pointer it was given, unsized by a coercion.
+    /// A cast to another type, or a pointer to a field of the value, would
+    /// make the handles of the allocation run different drop glue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `coerce` returns a pointer to another address.
+    pub unsafe fn unsize_unchecked<U: ?Sized>(
+        this: Self,
+        coerce: impl FnOnce(*const T) -> *const U,
+    ) -> Frc<U, P> {
+        let this = mem::ManuallyDrop::new(this);
+        let data = ptr::addr_of!((*this.ptr.as_ptr()).data);
+        let coerced = coerce(data);
+        assert!(
+            ptr::eq(coerced as *const u8, data as *const u8),
+            "Frc::unsize_unchecked must return the pointer it was given"
+        );
+
+        // Same address and layout, so only the pointer metadata changes.
+        let offset = data as *const u8 as usize - this.ptr.as_ptr() as *const u8 as usize;
+        let ptr = (coerced as *mut Inner<U>).wrapping_byte_sub(offset);
+        Frc {
+            weight: Cell::new(this.weight.get()),
+            ptr: NonNull::new_unchecked(ptr),
+            thread: this.thread,
+            _policy: PolicyMarker::new(),
+        }
+    }
+
+    /// Creates a new `FrcWeak` pointer to this allocation.
+    pub fn downgrade(this: &Self) -> FrcWeak<T, P> {
+        this.inner().downgrade_weight(P::INITIAL_WEIGHT);
+        FrcWeak {
+            weight: Cell::new(P::INITIAL_WEIGHT),
+            ptr: this.ptr,
+            thread: crate::registry::current_thread(),
+            _policy: PolicyMarker::new(),
+        }
+    }
+
+    /// Clones the `Frc` like `clone`, but returns an error instead of aborting
+    /// when the total weight of the allocation would overflow.
+    ///
+    /// The total weight is capped at `isize::MAX`, so an allocation holds at
+    /// most `isize::MAX / P::INITIAL_WEIGHT` handles that took the initial
+    /// weight from the shared counter: clones on other threads, upgrades and
+    /// `Singleton::get` results. With `DefaultWeight` that is `2^47` handles
+    /// on 64-bit targets and `2^23` on 32-bit ones.
+    pub fn try_clone(this: &Self) -> Result<Frc<T, P>, WeightOverflow> {
+        let current = crate::registry::current_thread();
+        let new_weight = if this.thread.is_current(current) {
+            split_weight::<P, _>(&this.weight, |add_wei| {
+                if this.inner().try_add_weight(add_wei) {
+                    Ok(())
+                } else {
+                    Err(WeightOverflow)
+                }
+            })?
+        } else if this.inner().try_add_weight(P::INITIAL_WEIGHT) {
+            P::INITIAL_WEIGHT
+        } else {
+            return Err(WeightOverflow);
+        };
+        Ok(Frc {
+            weight: Cell::new(new_weight),
+            ptr: this.ptr,
+            thread: current,
+            _policy: PolicyMarker::new(),
+        })
+    }
+
+    /// Returns a mutable reference into the given `Frc` if no other `Frc` or
+    /// `FrcWeak` points to the same allocation, that is when the weight of
+    /// this handle is the whole weight of the allocation.
+    #[inline]
+    pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+        if this.inner().is_unique(this.weight.get()) {
+            Some(unsafe { Self::get_mut_unchecked(this) })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference into the given `Frc` without any check.
+    ///
+    /// This is what the former `DerefMut` implementation did, and is meant
+    /// for code that guarantees uniqueness by other means.
+    ///
+    /// # Safety
+    ///
+    /// No other `Frc` or `FrcWeak` to the same allocation may be dereferenced
+    /// for the duration of the returned borrow.
+    #[inline]
+    pub unsafe fn get_mut_unchecked(this: &mut Self) -> &mut T {
+        this.ptr.as_mut().deref_mut()
+    }
+
+    /// Moves the value into a `Box` if this is the only `Frc` to it, otherwise
+    /// the `Frc` is handed back.
+    pub fn into_box(this: Self) -> Result<Box<T>, Self> {
+        if !this.inner().drop_weight_if_unique(this.weight.get()) {
+            return Err(this);
+        }
+        let this = mem::ManuallyDrop::new(this);
+        unsafe {
+            let data = ptr::addr_of_mut!((*this.ptr.as_ptr()).data);
+            let layout = Layout::for_value(&*data);
+            let mem = if layout.size() == 0 {
+                layout.align() as *mut u8
+            } else {
+                let mem = std::alloc::alloc(layout);
+                if mem.is_null() {
+                    std::alloc::handle_alloc_error(layout);
+                }
+                mem
+            };
+            ptr::copy_nonoverlapping(data as *const u8, mem, layout.size());
+            let boxed = with_addr(data, mem);
+            Self::release_weak(&this);
+            Ok(Box::from_raw(boxed))
+        }
+    }
+
+    // Gives up the weight the strong handles hold on the weak counter once
+    // the data is gone, freeing the allocation if no `FrcWeak` is left.
+    #[inline]
+    fn release_weak(this: &Self) {
+        drop(FrcWeak::<T, P> {
+            weight: Cell::new(1),
+            ptr: this.ptr,
+            thread: this.thread,
+            _policy: PolicyMarker::new(),
+        });
+    }
+
     #[inline]
     fn inner(&self) -> &Inner<T> {
         unsafe { self.ptr.as_ref() }
     }
 }
 
-unsafe impl<T: ?Sized + Send> Send for Frc<T> {}
-unsafe impl<T: ?Sized + Sync> Sync for Frc<T> {}
+impl<T: Clone, P: WeightPolicy> Frc<T, P> {
+    /// Returns the inner value if this is the only `Frc` to it, otherwise a
+    /// clone of it.
+    #[inline]
+    pub fn unwrap_or_clone(this: Self) -> T {
+        Frc::try_unwrap(this).unwrap_or_else(|this| (*this).clone())
+    }
+
+    /// Makes a mutable reference into the given `Frc`.
+    ///
+    /// If other handles point to the same allocation, the value is cloned into
+    /// a new allocation first, so this handle becomes unique.
+    #[inline]
+    pub fn make_mut(this: &mut Self) -> &mut T {
+        if !this.inner().is_unique(this.weight.get()) {
+            *this = Frc::new_in((**this).clone());
+        }
+        unsafe { Self::get_mut_unchecked(this) }
+    }
+}
 
-impl<T> Clone for Frc<T> {
+// Hands part of `weight` over to a new handle on the same thread. Once the
+// local weight can no longer be split, `refill` tops it up from the shared
+// counter to the refill weight of the policy instead.
+#[inline]
+pub(crate) fn split_weight<P: WeightPolicy, E>(
+    weight: &Cell<usize>,
+    refill: impl FnOnce(usize) -> Result<(), E>,
+) -> Result<usize, E> {
+    let mut existing_weight = weight.get();
+    if existing_weight >> P::SPLIT_SHIFT == 0 {
+        refill(P::REFILL_WEIGHT - existing_weight)?;
+        existing_weight = P::REFILL_WEIGHT;
+    }
+    let new_weight = existing_weight >> P::SPLIT_SHIFT;
+    weight.set(existing_weight - new_weight);
+    Ok(new_weight)
+}
+
+/// The error returned by `Frc::try_clone` when the total weight of an
+/// allocation would overflow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WeightOverflow;
+
+impl fmt::Display for WeightOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Frc weight overflow")
+    }
+}
+
+impl std::error::Error for WeightOverflow {}
+
+impl<T, P: WeightPolicy> Frc<[T], P> {
+    // Allocates an `Inner<[T]>` with room for `len` uninitialised elements.
+    unsafe fn allocate_for_slice(len: usize) -> *mut Inner<[T]> {
+        Inner::allocate_for_layout(Layout::array::<T>(len).unwrap(), P::INITIAL_WEIGHT, |mem| {
+            ptr::slice_from_raw_parts_mut(mem as *mut T, len) as *mut Inner<[T]>
+        })
+    }
+
+    // Moves the elements of `v` into a new allocation, leaving `v` empty.
+    unsafe fn from_vec_elements(v: &mut Vec<T>) -> Frc<[T], P> {
+        let inner = Self::allocate_for_slice(v.len());
+        let dst = ptr::addr_of_mut!((*inner).data) as *mut T;
+        ptr::copy_nonoverlapping(v.as_ptr(), dst, v.len());
+        v.set_len(0);
+        Self::from_inner(inner)
+    }
+}
+
+impl<T, P: WeightPolicy> From<Vec<T>> for Frc<[T], P> {
+    #[inline]
+    fn from(mut v: Vec<T>) -> Frc<[T], P> {
+        unsafe { Self::from_vec_elements(&mut v) }
+    }
+}
+
+impl<T: Clone, P: WeightPolicy> From<&[T]> for Frc<[T], P> {
+    fn from(v: &[T]) -> Frc<[T], P> {
+        // Frees the allocation and the elements cloned so far if a clone panics.
+        struct Guard<T> {
+            inner: *mut Inner<[T]>,
+            layout: Layout,
+            elems: *mut T,
+            written: usize,
+        }
+
+        impl<T> Drop for Guard<T> {
+            fn drop(&mut self) {
+                unsafe {
+                    let written = ptr::slice_from_raw_parts_mut(self.elems, self.written);
+                    ptr::drop_in_place(written);
+                    std::alloc::dealloc(self.inner as *mut u8, self.layout);
+                }
+            }
+        }
+
+        unsafe {
+            let inner = Self::allocate_for_slice(v.len());
+            let elems = ptr::addr_of_mut!((*inner).data) as *mut T;
+            let mut guard = Guard {
+                inner,
+                layout: inner_layout(Layout::array::<T>(v.len()).unwrap()),
+                elems,
+                written: 0,
+            };
+            for (i, item) in v.iter().enumerate() {
+                ptr::write(elems.add(i), item.clone());
+                guard.written += 1;
+            }
+            mem::forget(guard);
+            Self::from_inner(inner)
+        }
+    }
+}
+
+impl<T, P: WeightPolicy> FromIterator<T> for Frc<[T], P> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Frc<[T], P> {
+        Frc::from(iter.into_iter().collect::<Vec<T>>())
+    }
+}
+
+impl<P: WeightPolicy> Frc<str, P> {
+    // Reinterprets a byte slice that is known to hold valid UTF-8.
+    #[inline]
+    unsafe fn from_utf8_unchecked(bytes: Frc<[u8], P>) -> Frc<str, P> {
+        let bytes = mem::ManuallyDrop::new(bytes);
+        Frc {
+            weight: Cell::new(bytes.weight.get()),
+            ptr: NonNull::new_unchecked(bytes.ptr.as_ptr() as *mut Inner<str>),
+            thread: bytes.thread,
+            _policy: PolicyMarker::new(),
+        }
+    }
+}
+
+impl<P: WeightPolicy> From<&str> for Frc<str, P> {
+    #[inline]
+    fn from(v: &str) -> Frc<str, P> {
+        unsafe { Frc::from_utf8_unchecked(Frc::from(v.as_bytes())) }
+    }
+}
+
+impl<P: WeightPolicy> From<String> for Frc<str, P> {
+    #[inline]
+    fn from(v: String) -> Frc<str, P> {
+        unsafe { Frc::from_utf8_unchecked(Frc::from(v.into_bytes())) }
+    }
+}
+
+/// Converts an `Frc<T>` into an `Frc<U>` by an unsizing coercion, typically
+/// to a trait object: `frc_unsize!(frc, dyn Display)`.
+///
+/// Only a coercion type checks, so the result always shares the value of
+/// the handle it was made from.
+#[macro_export]
+macro_rules! frc_unsize {
+    ($frc:expr, $target:ty) => {{
+        let frc = $frc;
+        // The closure returns its argument through a coercion, never a cast.
+        unsafe { $crate::Frc::unsize_unchecked(frc, |ptr| -> *const $target { ptr }) }
+    }};
+}
+
+impl<P: WeightPolicy> Frc<dyn Any + Send + Sync, P> {
+    /// Attempts to downcast the `Frc<dyn Any + Send + Sync>` to a concrete type,
+    /// keeping the weight and thread of the handle.
+    pub fn downcast<T: Any + Send + Sync>(self) -> Result<Frc<T, P>, Self> {
+        if !(*self).is::<T>() {
+            return Err(self);
+        }
+        let this = mem::ManuallyDrop::new(self);
+        Ok(Frc {
+            weight: Cell::new(this.weight.get()),
+            ptr: this.ptr.cast(),
+            thread: this.thread,
+            _policy: PolicyMarker::new(),
+        })
+    }
+}
+
+// Returns `ptr` pointing at `addr` instead, keeping its metadata. The result
+// derives from `addr`, so it may access the allocation `addr` points into,
+// like std's `set_ptr_value`.
+fn with_addr<T: ?Sized>(mut ptr: *mut T, addr: *mut u8) -> *mut T {
+    // The address is the first word of a pointer, thin or wide.
+    unsafe { *(&mut ptr as *mut *mut T as *mut *mut u8) = addr };
+    ptr
+}
+
+impl<T: ?Sized, P: WeightPolicy> From<Box<T>> for Frc<T, P> {
+    fn from(v: Box<T>) -> Frc<T, P> {
+        unsafe {
+            let value_layout = Layout::for_value(&*v);
+            let src = Box::into_raw(v);
+            // Reuse the metadata of the boxed pointer and only move its address
+            // over to the new allocation.
+            let inner = Inner::allocate_for_layout(value_layout, P::INITIAL_WEIGHT, |mem| {
+                with_addr(src as *mut Inner<T>, mem)
+            });
+            let dst = ptr::addr_of_mut!((*inner).data) as *mut u8;
+            ptr::copy_nonoverlapping(src as *const u8, dst, value_layout.size());
+
+            // Free the box without dropping the value that was moved out.
+            if value_layout.size() != 0 {
+                std::alloc::dealloc(src as *mut u8, value_layout);
+            }
+            Self::from_inner(inner)
+        }
+    }
+}
+
+// Clones on different threads hand out `&T` concurrently and the last one
+// to go drops `T` wherever it is, so both need `T: Send + Sync` like `Arc`.
+unsafe impl<T: ?Sized + Send + Sync, P: WeightPolicy> Send for Frc<T, P> {}
+unsafe impl<T: ?Sized + Send + Sync, P: WeightPolicy> Sync for Frc<T, P> {}
+
+impl<T: ?Sized, P: WeightPolicy> Clone for Frc<T, P> {
     fn clone(&self) -> Self {
-        let tno = unsafe { *crate::get_thread_number() };
-        if self.thread_no == tno {
+        let current = crate::registry::current_thread();
+        if self.thread.is_current(current) {
             // Reduce Current Weight
-            let existing_weight = self.weight.get();
-            let new_weight = if existing_weight > 1 {
-                let new_weight = existing_weight >> 1;
-                self.weight.set(new_weight);
-                new_weight
-            } else {
-                let add_wei = DEFAULT_ADD_WEIGHT - existing_weight;
+            let Ok(new_weight) = split_weight::<P, _>(&self.weight, |add_wei| {
                 self.inner().add_weight(add_wei);
-                self.weight.set(DEFAULT_WEIGHT);
-                DEFAULT_WEIGHT
-            };
+                Ok::<(), Infallible>(())
+            });
             Frc {
                 weight: Cell::new(new_weight),
                 ptr: self.ptr,
-                thread_no: tno,
+                thread: current,
+                _policy: PolicyMarker::new(),
             }
         } else {
             // Get from inner directly
-            self.inner().add_weight(DEFAULT_WEIGHT);
+            self.inner().add_weight(P::INITIAL_WEIGHT);
 
             Frc {
-                weight: Cell::new(DEFAULT_WEIGHT),
+                weight: Cell::new(P::INITIAL_WEIGHT),
                 ptr: self.ptr,
-                thread_no: tno,
+                thread: current,
+                _policy: PolicyMarker::new(),
             }
         }
     }
 }
 
-impl<T: ?Sized> Drop for Frc<T> {
+impl<T: ?Sized, P: WeightPolicy> Drop for Frc<T, P> {
     fn drop(&mut self) {
         let ptr = unsafe { self.ptr.as_ref() };
         let existing_weight = self.weight.get();
         if ptr.drop_weight(existing_weight) > 0 {
             return;
         }
+        atomic::fence(atomic::Ordering::Acquire);
 
-        let ptr = self.ptr.as_ptr();
-        let data = unsafe { Box::from_raw(ptr) };
-        drop(data);
+        // Drop the data now, the allocation itself goes away together with
+        // the weight the strong handles hold on the weak counter.
+        unsafe { ptr::drop_in_place(ptr::addr_of_mut!((*self.ptr.as_ptr()).data)) };
+        Self::release_weak(self);
     }
 }
 
-impl<T> Deref for Frc<T> {
+impl<T: ?Sized, P: WeightPolicy> Deref for Frc<T, P> {
     /// The resulting type after dereferencing
     type Target = T;
 
@@ -100,80 +568,73 @@ impl<T> Deref for Frc<T> {
     }
 }
 
-impl<T> DerefMut for Frc<T> {
-    #[inline]
-    fn deref_mut(&mut self) -> &mut T {
-        unsafe { self.ptr.as_mut() }.deref_mut()
-    }
-}
-
-impl<T: fmt::Display> fmt::Display for Frc<T> {
+impl<T: ?Sized + fmt::Display, P: WeightPolicy> fmt::Display for Frc<T, P> {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Display::fmt(self.inner(), f)
     }
 }
 
-impl<T: fmt::Debug> fmt::Debug for Frc<T> {
+impl<T: ?Sized + fmt::Debug, P: WeightPolicy> fmt::Debug for Frc<T, P> {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Debug::fmt(self.inner(), f)
     }
 }
 
-impl<T: PartialEq> PartialEq for Frc<T> {
+impl<T: ?Sized + PartialEq, P: WeightPolicy> PartialEq for Frc<T, P> {
     #[inline]
-    fn eq(&self, other: &Frc<T>) -> bool {
+    fn eq(&self, other: &Frc<T, P>) -> bool {
         self.inner() == other.inner()
     }
 }
 
-impl<T: PartialOrd> PartialOrd for Frc<T> {
+impl<T: ?Sized + PartialOrd, P: WeightPolicy> PartialOrd for Frc<T, P> {
     #[inline]
-    fn partial_cmp(&self, other: &Frc<T>) -> Option<Ordering> {
+    fn partial_cmp(&self, other: &Frc<T, P>) -> Option<Ordering> {
         self.inner().partial_cmp(other.inner())
     }
 
     #[inline]
-    fn lt(&self, other: &Frc<T>) -> bool {
+    fn lt(&self, other: &Frc<T, P>) -> bool {
         self.inner() < other.inner()
     }
 
     #[inline]
-    fn le(&self, other: &Frc<T>) -> bool {
+    fn le(&self, other: &Frc<T, P>) -> bool {
         self.inner() <= other.inner()
     }
 
     #[inline]
-    fn gt(&self, other: &Frc<T>) -> bool {
+    fn gt(&self, other: &Frc<T, P>) -> bool {
         self.inner() > other.inner()
     }
 
     #[inline]
-    fn ge(&self, other: &Frc<T>) -> bool {
+    fn ge(&self, other: &Frc<T, P>) -> bool {
         self.inner() >= other.inner()
     }
 }
 
-impl<T: Ord> Ord for Frc<T> {
+impl<T: ?Sized + Ord, P: WeightPolicy> Ord for Frc<T, P> {
     #[inline]
-    fn cmp(&self, other: &Frc<T>) -> Ordering {
+    fn cmp(&self, other: &Frc<T, P>) -> Ordering {
         self.inner().cmp(other.inner())
     }
 }
 
-impl<T: Eq> Eq for Frc<T> {}
+impl<T: ?Sized + Eq, P: WeightPolicy> Eq for Frc<T, P> {}
 
 // Default Impl
-impl<T: Default> Default for Frc<T> {
+impl<T: Default, P: WeightPolicy> Default for Frc<T, P> {
     #[inline]
-    fn default() -> Frc<T> {
-        Frc::new(Default::default())
+    fn default() -> Frc<T, P> {
+        Frc::new_in(Default::default())
     }
 }
 
 // Hash Impl
-impl<T: Hash> Hash for Frc<T> {
+impl<T: ?Sized + Hash, P: WeightPolicy> Hash for Frc<T, P> {
     #[inline]
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.inner().hash(state)
@@ -181,14 +642,14 @@ impl<T: Hash> Hash for Frc<T> {
 }
 
 // AsRef Impl
-impl<T> AsRef<T> for Frc<T> {
+impl<T: ?Sized, P: WeightPolicy> AsRef<T> for Frc<T, P> {
     fn as_ref(&self) -> &T {
         &self.inner().data
     }
 }
 
 // Serde Implementation
-impl<T: serde::Serialize> serde::Serialize for Frc<T> {
+impl<T: ?Sized + serde::Serialize, P: WeightPolicy> serde::Serialize for Frc<T, P> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
@@ -197,11 +658,11 @@ impl<T: serde::Serialize> serde::Serialize for Frc<T> {
     }
 }
 
-impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Frc<T> {
+impl<'de, T: serde::Deserialize<'de>, P: WeightPolicy> serde::Deserialize<'de> for Frc<T, P> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        Ok(Frc::new(T::deserialize(deserializer)?))
+        Ok(Frc::new_in(T::deserialize(deserializer)?))
     }
 }
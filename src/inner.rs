@@ -1,15 +1,32 @@
 use std::{
+    alloc::{self, Layout},
     cmp::Ordering,
     fmt,
     hash::{Hash, Hasher},
     ops::{Deref, DerefMut},
-    sync::atomic,
+    ptr,
 };
 
+use crate::sync::atomic;
+
+// Largest total either counter may reach. Like `Arc`, going over it aborts
+// the process, which leaves enough headroom that concurrent additions can
+// never wrap the counter before one of them notices.
+pub(crate) const MAX_WEIGHT: usize = isize::MAX as usize;
+
+// Value of `weak_weight` while `Inner::is_unique` is running.
+const WEAK_LOCKED: usize = usize::MAX;
+
 // The Wrc `Inner` value uses `AtomicUsize` to store the total weight value,
 // meaning that mutations of the weight are thread-safe.
+//
+// `weak_weight` is the total weight of all `FrcWeak` handles plus one unit
+// held collectively by the strong handles, so the allocation outlives the
+// data until the last weak handle is gone.
+#[repr(C)]
 pub struct Inner<T: ?Sized> {
     weight: atomic::AtomicUsize,
+    weak_weight: atomic::AtomicUsize,
     pub(crate) data: T,
 }
 
@@ -18,36 +35,221 @@ impl<T> Inner<T> {
     pub fn new(data: T, starting_weight: usize) -> Inner<T> {
         Inner {
             weight: atomic::AtomicUsize::new(starting_weight),
+            weak_weight: atomic::AtomicUsize::new(1),
             data,
         }
     }
 }
 
 impl<T: ?Sized> Inner<T> {
+    // Allocates an `Inner` able to hold a value with `value_layout` and
+    // initialises its weights. The data is left uninitialised, and
+    // `mem_to_inner` attaches the pointer metadata for unsized `T`.
+    pub(crate) unsafe fn allocate_for_layout(
+        value_layout: Layout,
+        starting_weight: usize,
+        mem_to_inner: impl FnOnce(*mut u8) -> *mut Inner<T>,
+    ) -> *mut Inner<T> {
+        let layout = inner_layout(value_layout);
+        let mem = alloc::alloc(layout);
+        if mem.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+
+        let inner = mem_to_inner(mem);
+        ptr::addr_of_mut!((*inner).weight).write(atomic::AtomicUsize::new(starting_weight));
+        ptr::addr_of_mut!((*inner).weak_weight).write(atomic::AtomicUsize::new(1));
+        inner
+    }
+
     #[inline]
     pub fn add_weight(&self, weight: usize) {
-        self.weight
+        let fetched = self
+            .weight
             .fetch_add(weight, std::sync::atomic::Ordering::Relaxed);
+        check_added(fetched, weight);
+    }
+
+    // Adds `weight` unless the total would go over `MAX_WEIGHT`, leaving the
+    // counter untouched in that case.
+    #[inline]
+    pub fn try_add_weight(&self, weight: usize) -> bool {
+        self.weight
+            .fetch_update(
+                std::sync::atomic::Ordering::Relaxed,
+                std::sync::atomic::Ordering::Relaxed,
+                |current| {
+                    current
+                        .checked_add(weight)
+                        .filter(|total| *total <= MAX_WEIGHT)
+                },
+            )
+            .is_ok()
     }
 
+    // Releases `weight` and returns what is left. The caller that sees zero
+    // must issue an `Acquire` fence before touching the data, so that every
+    // write made through other handles happens before the drop.
     #[inline]
     pub fn drop_weight(&self, weight: usize) -> usize {
         let fetched = self
             .weight
-            .fetch_sub(weight, std::sync::atomic::Ordering::Relaxed);
-        fetched - weight
+            .fetch_sub(weight, std::sync::atomic::Ordering::Release);
+        check_dropped(fetched, weight)
+    }
+
+    // Drops `weight` only if it is the whole remaining weight, the same
+    // comparison `drop_weight` makes when it reaches zero.
+    #[inline]
+    pub fn drop_weight_if_unique(&self, weight: usize) -> bool {
+        self.weight
+            .compare_exchange(
+                weight,
+                0,
+                std::sync::atomic::Ordering::Acquire,
+                std::sync::atomic::Ordering::Relaxed,
+            )
+            .is_ok()
     }
 
     #[inline]
     pub fn get_weight(&self, order: std::sync::atomic::Ordering) -> usize {
         self.weight.load(order)
     }
+
+    // Sets the weight of an `Inner` created with no strong weight once its
+    // data is written, pairing with the `Acquire` in `add_weight_if_alive`.
+    #[inline]
+    pub fn publish_weight(&self, weight: usize) {
+        self.weight
+            .store(weight, std::sync::atomic::Ordering::Release);
+    }
+
+    // Adds weight only while the data is still alive, used by `FrcWeak::upgrade`.
+    #[inline]
+    pub fn add_weight_if_alive(&self, weight: usize) -> bool {
+        self.weight
+            .fetch_update(
+                std::sync::atomic::Ordering::Acquire,
+                std::sync::atomic::Ordering::Relaxed,
+                |current| {
+                    if current == 0 {
+                        None
+                    } else {
+                        check_added(current, weight);
+                        Some(current + weight)
+                    }
+                },
+            )
+            .is_ok()
+    }
+
+    #[inline]
+    pub fn add_weak_weight(&self, weight: usize) {
+        let fetched = self
+            .weak_weight
+            .fetch_add(weight, std::sync::atomic::Ordering::Relaxed);
+        check_added(fetched, weight);
+    }
+
+    // Adds weak weight for a new `FrcWeak`, waiting while `is_unique` holds
+    // the weak counter locked.
+    #[inline]
+    pub fn downgrade_weight(&self, weight: usize) {
+        let mut current = self.weak_weight.load(std::sync::atomic::Ordering::Relaxed);
+        loop {
+            if current == WEAK_LOCKED {
+                std::hint::spin_loop();
+                current = self.weak_weight.load(std::sync::atomic::Ordering::Relaxed);
+                continue;
+            }
+            check_added(current, weight);
+            match self.weak_weight.compare_exchange_weak(
+                current,
+                current + weight,
+                std::sync::atomic::Ordering::Acquire,
+                std::sync::atomic::Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    // Same as `drop_weight`, for the weak counter.
+    #[inline]
+    pub fn drop_weak_weight(&self, weight: usize) -> usize {
+        let fetched = self
+            .weak_weight
+            .fetch_sub(weight, std::sync::atomic::Ordering::Release);
+        check_dropped(fetched, weight)
+    }
+
+    // Returns whether a strong handle holding `weight` is the only handle,
+    // strong or weak. The weak counter is locked while the strong weight is
+    // checked so that no weak handle can appear and upgrade in between.
+    #[inline]
+    pub fn is_unique(&self, weight: usize) -> bool {
+        if self
+            .weak_weight
+            .compare_exchange(
+                1,
+                WEAK_LOCKED,
+                std::sync::atomic::Ordering::Acquire,
+                std::sync::atomic::Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            return false;
+        }
+        let unique = self.get_weight(std::sync::atomic::Ordering::Acquire) == weight;
+        self.weak_weight
+            .store(1, std::sync::atomic::Ordering::Release);
+        unique
+    }
+}
+
+#[inline]
+fn check_added(fetched: usize, weight: usize) {
+    if fetched
+        .checked_add(weight)
+        .is_none_or(|total| total > MAX_WEIGHT)
+    {
+        weight_error("overflow");
+    }
+}
+
+#[inline]
+fn check_dropped(fetched: usize, weight: usize) -> usize {
+    match fetched.checked_sub(weight) {
+        Some(remaining) => remaining,
+        None => weight_error("underflow"),
+    }
+}
+
+// A corrupted weight can free the data while handles still point to it, so
+// there is no safe way to continue.
+#[cold]
+fn weight_error(kind: &str) -> ! {
+    log::error!("Frc weight {}, aborting", kind);
+    std::process::abort()
+}
+
+// The layout of an `Inner` holding a value with `value_layout`, matching the
+// `repr(C)` layout of `Inner<T>`.
+#[inline]
+pub(crate) fn inner_layout(value_layout: Layout) -> Layout {
+    Layout::new::<Inner<()>>()
+        .extend(value_layout)
+        .unwrap()
+        .0
+        .pad_to_align()
 }
 
-unsafe impl<T: ?Sized + Send> Send for Inner<T> {}
-unsafe impl<T: ?Sized + Sync> Sync for Inner<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Send for Inner<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for Inner<T> {}
 
-impl<T> Deref for Inner<T> {
+impl<T: ?Sized> Deref for Inner<T> {
     type Target = T;
 
     #[inline]
@@ -56,7 +258,7 @@ impl<T> Deref for Inner<T> {
     }
 }
 
-impl<T> DerefMut for Inner<T> {
+impl<T: ?Sized> DerefMut for Inner<T> {
     #[inline]
     fn deref_mut(&mut self) -> &mut T {
         &mut self.data
@@ -65,7 +267,7 @@ impl<T> DerefMut for Inner<T> {
 
 impl<T> fmt::Display for Inner<T>
 where
-    T: fmt::Display,
+    T: ?Sized + fmt::Display,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Display::fmt(&self.data, f)
@@ -74,7 +276,7 @@ where
 
 impl<T> fmt::Debug for Inner<T>
 where
-    T: fmt::Debug,
+    T: ?Sized + fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Debug::fmt(&self.data, f)
@@ -87,7 +289,7 @@ impl<T: ?Sized + PartialEq> PartialEq for Inner<T> {
     }
 }
 
-impl<T: PartialOrd> PartialOrd for Inner<T> {
+impl<T: ?Sized + PartialOrd> PartialOrd for Inner<T> {
     fn partial_cmp(&self, other: &Inner<T>) -> Option<Ordering> {
         self.data.partial_cmp(&other.data)
     }
@@ -109,15 +311,15 @@ impl<T: PartialOrd> PartialOrd for Inner<T> {
     }
 }
 
-impl<T: Ord> Ord for Inner<T> {
+impl<T: ?Sized + Ord> Ord for Inner<T> {
     fn cmp(&self, other: &Inner<T>) -> Ordering {
         self.data.cmp(&other.data)
     }
 }
 
-impl<T: Eq> Eq for Inner<T> {}
+impl<T: ?Sized + Eq> Eq for Inner<T> {}
 
-impl<T: Hash> Hash for Inner<T> {
+impl<T: ?Sized + Hash> Hash for Inner<T> {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.data.hash(state)
     }
@@ -1,20 +1,33 @@
-use std::ffi::c_void;
-
-extern "C" {
-    pub fn get_thread_number() -> *mut u32;
-    pub fn initialize_thread_number();
-    pub fn uninitialize_thread_number();
-    pub fn store_local_pointer(pos: i32, ptr: *mut c_void);
-    pub fn get_local_pointer(pos: i32) -> *mut c_void;
-}
-
+#[cfg(doctest)]
+mod compile_fail;
+mod counter;
+pub mod epoch;
+mod group;
 mod imp;
 mod inner;
+mod local_vec;
+mod policy;
+pub mod raw;
+mod registry;
+#[cfg(feature = "reload")]
+mod reload;
+mod sharded_lock;
 mod singleton;
+mod slot;
+mod sync;
+pub mod thread;
+mod weak;
 
-pub use imp::Frc;
-pub use singleton::Singleton;
-#[cfg(test)]
+pub use counter::Counter;
+pub use group::{SingletonGroup, SingletonTransaction};
+pub use imp::{Frc, WeightOverflow};
+pub use policy::{DefaultWeight, WeightPolicy};
+#[cfg(feature = "reload")]
+pub use reload::{Format, ReloadError, ReloadWatcher, ReloadableSingleton};
+pub use sharded_lock::{FrcShardedLock, FrcShardedLockReadGuard, FrcShardedLockWriteGuard};
+pub use singleton::{Changed, HistoryEntry, NotInHistory, Singleton};
+pub use weak::FrcWeak;
+#[cfg(all(test, not(loom)))]
 mod bench {
     struct RandGen {}
 
@@ -24,7 +37,7 @@ mod bench {
         }
 
         fn gen(&self) -> u32 {
-            unsafe { *crate::get_thread_number() }
+            crate::thread::current_thread_number().unwrap_or_default()
             // let ptr = Box::into_raw(Box::new(123));
             // let ret = (ptr as usize % 10) as u32;
             // unsafe {
@@ -36,9 +49,7 @@ mod bench {
 
     #[test]
     fn run_bench() {
-        unsafe {
-            crate::initialize_thread_number();
-        }
+        let _guard = crate::thread::register();
         use std::time::Instant;
         const RUNCNT: u32 = 100;
         let mut val = 0;
@@ -88,10 +99,6 @@ mod bench {
             elapsed += c_elapsed;
         }
         println!("FRC Elapsed: {:.2?} : {}", elapsed / RUNCNT, val);
-
-        unsafe {
-            crate::uninitialize_thread_number();
-        }
     }
 
     fn run_rc() -> u32 {
@@ -125,67 +132,553 @@ mod bench {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(loom)))]
 mod tests {
-    use std::{
-        collections::{BTreeMap, BTreeSet},
-        ops::Add,
-        sync::atomic::AtomicU32,
-    };
+    use std::{collections::BTreeSet, ops::Add, sync::atomic::AtomicU32};
 
-    use crate::{
-        get_local_pointer, get_thread_number, initialize_thread_number, store_local_pointer,
-        uninitialize_thread_number,
-    };
+    thread_local! {
+        static WORKER_GUARD: std::cell::RefCell<Option<crate::thread::ThreadGuard>> =
+            const { std::cell::RefCell::new(None) };
+    }
 
     #[test]
     fn run_test() {
+        registration_test();
+        slot_test();
+        local_vec_test();
+        sharded_lock_test();
+        epoch_test();
         thread_counter_test();
         singleton_test();
+        singleton_stress_test();
+        singleton_init_test();
+        singleton_rcu_test();
+        singleton_watch_test();
+        singleton_local_test();
+        singleton_tls_destructor_test();
+        singleton_history_test();
+        singleton_group_test();
+        #[cfg(feature = "reload")]
+        reloadable_singleton_test();
+        weak_test();
+        unsized_test();
+        trait_object_test();
+        mutation_test();
+        unwrap_test();
+        try_clone_test();
+        policy_test();
         tokio_test();
     }
 
+    fn registration_test() {
+        std::thread::spawn(|| {
+            if crate::thread::current_thread_number().is_some() {
+                panic!("new thread is already registered");
+            }
+            let guard = crate::thread::register();
+            let tno = crate::thread::current_thread_number().expect("thread is registered");
+            drop(crate::thread::register());
+            if crate::thread::current_thread_number() != Some(tno) {
+                panic!("nested guard released the registration");
+            }
+            drop(guard);
+            if crate::thread::current_thread_number().is_some() {
+                panic!("thread is still registered after its guard was dropped");
+            }
+            if unsafe { *crate::raw::get_thread_number() } != crate::raw::UNREGISTERED {
+                panic!("unregistered thread does not read the sentinel");
+            }
+        })
+        .join()
+        .unwrap();
+
+        // Using an `Frc` registers the thread until it exits.
+        std::thread::spawn(|| {
+            let frc = crate::Frc::new(1);
+            let tno = crate::thread::current_thread_number().expect("registered on first use");
+            if frc.thread.number() != tno || tno == crate::raw::UNREGISTERED {
+                panic!("handle captured the wrong thread number");
+            }
+            // A guard takes over the lazy registration.
+            drop(crate::thread::register());
+            if crate::thread::current_thread_number().is_some() {
+                panic!("guard did not release the adopted registration");
+            }
+
+            // The number may be handed out again, but the handle is stale
+            // and clones through the shared counter.
+            let _guard = crate::thread::register();
+            let weight = frc.weight.get();
+            let clone = frc.clone();
+            if frc.weight.get() != weight || clone.thread == frc.thread {
+                panic!("stale handle split its weight");
+            }
+        })
+        .join()
+        .unwrap();
+
+        std::thread::spawn(|| unsafe {
+            use crate::thread::RegistrationError;
+            if crate::raw::uninitialize_thread_number() != Err(RegistrationError::NotRegistered) {
+                panic!("uninitialized an unregistered thread");
+            }
+            crate::raw::initialize_thread_number().unwrap();
+            if crate::raw::initialize_thread_number() != Err(RegistrationError::AlreadyRegistered) {
+                panic!("initialized a registered thread twice");
+            }
+            crate::raw::uninitialize_thread_number().unwrap();
+        })
+        .join()
+        .unwrap();
+    }
+
+    fn slot_test() {
+        struct Counted(std::sync::Arc<AtomicU32>);
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+
+        let drops = std::sync::Arc::new(AtomicU32::new(0));
+        let slot = std::sync::Arc::new(crate::thread::ThreadSlot::<Counted>::new());
+        let dropped = || drops.load(std::sync::atomic::Ordering::Relaxed);
+
+        let (d, s) = (drops.clone(), slot.clone());
+        std::thread::spawn(move || {
+            let guard = crate::thread::register();
+            s.set(Counted(d.clone()));
+            s.set(Counted(d.clone()));
+            if d.load(std::sync::atomic::Ordering::Relaxed) != 1 || !s.is_set() {
+                panic!("replaced value was not dropped");
+            }
+            drop(guard);
+            if d.load(std::sync::atomic::Ordering::Relaxed) != 2 || s.is_set() {
+                panic!("value outlived the registration");
+            }
+            s.set(Counted(d));
+        })
+        .join()
+        .unwrap();
+        // The last value registered the thread again and went with it.
+        if dropped() != 3 {
+            panic!("value was not dropped at thread exit");
+        }
+
+        let other = crate::thread::ThreadSlot::<Counted>::new();
+        slot.set(Counted(drops.clone()));
+        if other.is_set() || !slot.with(|v| v.is_some()) {
+            panic!("slots share a value");
+        }
+        drop(slot);
+        if dropped() != 4 {
+            panic!("dropping the slot kept the value of its thread");
+        }
+    }
+
+    fn local_vec_test() {
+        const THREADS: u32 = 4;
+        let mut values = crate::thread::ThreadLocalVec::new();
+        let counter = crate::Counter::new();
+        // Keeps every thread, and so its number, alive until all have a value.
+        let barrier = std::sync::Barrier::new(THREADS as usize);
+        std::thread::scope(|s| {
+            for i in 0..THREADS {
+                let (values, counter, barrier) = (&values, &counter, &barrier);
+                s.spawn(move || {
+                    let _guard = crate::thread::register();
+                    let value = values.get_or(|| AtomicU32::new(i));
+                    barrier.wait();
+                    // The value is created once per thread.
+                    if values
+                        .get_or(|| AtomicU32::new(u32::MAX))
+                        .load(std::sync::atomic::Ordering::Relaxed)
+                        != i
+                        || !std::ptr::eq(value, values.get().unwrap())
+                    {
+                        panic!("thread did not get its own value");
+                    }
+                    for _ in 0..1000 {
+                        counter.increment();
+                    }
+                });
+            }
+        });
+        let mut seen: Vec<u32> = values.iter_mut().map(|v| *v.get_mut()).collect();
+        seen.sort_unstable();
+        if seen != (0..THREADS).collect::<Vec<_>>() {
+            panic!("values of the threads were lost: {:?}", seen);
+        }
+        if counter.sum() != THREADS as u64 * 1000 {
+            panic!("counter lost increments: {}", counter.sum());
+        }
+    }
+
+    fn sharded_lock_test() {
+        const THREADS: usize = 4;
+        let lock = crate::FrcShardedLock::new((0_u64, 0_u64));
+        std::thread::scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|| {
+                    for i in 0..1000 {
+                        if i % 10 == 0 {
+                            let mut pair = lock.write();
+                            pair.0 += 1;
+                            pair.1 += 1;
+                        } else {
+                            let pair = lock.read();
+                            if pair.0 != pair.1 {
+                                panic!("read a half written value: {:?}", *pair);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        if lock.into_inner() != (THREADS as u64 * 100, THREADS as u64 * 100) {
+            panic!("writes were lost");
+        }
+    }
+
+    fn epoch_test() {
+        use std::sync::{atomic::Ordering, mpsc, Arc};
+
+        let freed = Arc::new(AtomicU32::new(0));
+        let flush = || {
+            for _ in 0..3 {
+                crate::epoch::pin().flush();
+            }
+        };
+
+        let guard = crate::epoch::pin();
+        let f = freed.clone();
+        guard.defer(move || {
+            f.fetch_add(1, Ordering::Relaxed);
+        });
+        guard.flush();
+        if freed.load(Ordering::Relaxed) != 0 {
+            panic!("deferred function ran while its thread was pinned");
+        }
+        drop(guard);
+        flush();
+        if freed.load(Ordering::Relaxed) != 1 {
+            panic!("deferred function did not run after unpinning");
+        }
+
+        // A pinned thread holds the epoch back until it unpins, even when it
+        // is unregistered in between.
+        let (pinned_tx, pinned_rx) = mpsc::channel();
+        let (unpin_tx, unpin_rx) = mpsc::channel::<()>();
+        let reader = std::thread::spawn(move || {
+            let registration = crate::thread::register();
+            let guard = crate::epoch::pin();
+            pinned_tx.send(()).unwrap();
+            unpin_rx.recv().unwrap();
+            drop(registration);
+            if crate::thread::current_thread_number().is_none() {
+                panic!("pinned thread was unregistered");
+            }
+            drop(guard);
+            if crate::thread::current_thread_number().is_some() {
+                panic!("unregistration was lost when unpinning");
+            }
+        });
+        pinned_rx.recv().unwrap();
+        let value = Box::into_raw(Box::new(String::from("frc")));
+        let f = freed.clone();
+        {
+            let guard = crate::epoch::pin();
+            unsafe { guard.defer_destroy(value) };
+            guard.defer(move || {
+                f.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+        flush();
+        if freed.load(Ordering::Relaxed) != 1 {
+            panic!("deferred function ran while another thread was pinned");
+        }
+        unpin_tx.send(()).unwrap();
+        reader.join().unwrap();
+        flush();
+        if freed.load(Ordering::Relaxed) != 2 {
+            panic!("unregistered thread held the epoch back");
+        }
+
+        // A deferred function panicking inside `pin` leaves the thread
+        // unpinned, and the functions queued after it are run later.
+        let tester = std::thread::current().id();
+        {
+            let guard = crate::epoch::pin();
+            guard.defer(move || {
+                // Other threads may collect it too, only panic here.
+                if std::thread::current().id() == tester {
+                    panic!("deferred function panicked");
+                }
+            });
+            let f = freed.clone();
+            guard.defer(move || {
+                f.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+        let _ = std::panic::catch_unwind(|| {
+            for _ in 0..1000 {
+                drop(crate::epoch::pin());
+            }
+        });
+        if crate::epoch::is_pinned() {
+            panic!("thread stayed pinned after a deferred function panicked");
+        }
+        flush();
+        if freed.load(Ordering::Relaxed) != 3 {
+            panic!("deferred function after a panicking one was lost");
+        }
+    }
+
+    fn policy_test() {
+        struct Shallow;
+
+        impl crate::WeightPolicy for Shallow {
+            const INITIAL_WEIGHT: usize = 4;
+            const SPLIT_SHIFT: u32 = 2;
+            const REFILL_WEIGHT: usize = 16;
+        }
+
+        struct DropCount(std::sync::Arc<AtomicU32>);
+
+        impl Drop for DropCount {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+
+        let drops = std::sync::Arc::new(AtomicU32::new(0));
+        let value = crate::Frc::with_policy(DropCount(drops.clone()), Shallow);
+        let mut clones = (0..100).map(|_| value.clone()).collect::<Vec<_>>();
+        let remote = clones.pop().unwrap();
+        let handle = std::thread::spawn(move || {
+            let remote_clones = (0..100).map(|_| remote.clone()).collect::<Vec<_>>();
+            remote_clones.len()
+        });
+        if handle.join().unwrap() != 100 {
+            panic!("remote clones failed");
+        }
+        drop(clones);
+        if drops.load(std::sync::atomic::Ordering::Relaxed) != 0 {
+            panic!("value dropped while a handle is alive");
+        }
+        drop(value);
+        if drops.load(std::sync::atomic::Ordering::Relaxed) != 1 {
+            panic!("value was not dropped exactly once");
+        }
+    }
+
+    fn try_clone_test() {
+        let value = crate::Frc::new(1_u32);
+        let local = crate::Frc::try_clone(&value).expect("weight is far from the limit");
+        let remote = std::thread::spawn(move || crate::Frc::try_clone(&local).map(|v| *v))
+            .join()
+            .unwrap();
+        if remote != Ok(1) {
+            panic!("try_clone failed: {:?}", remote);
+        }
+
+        let full = crate::inner::Inner::new((), crate::inner::MAX_WEIGHT);
+        if full.try_add_weight(1)
+            || full.get_weight(std::sync::atomic::Ordering::Relaxed) != crate::inner::MAX_WEIGHT
+        {
+            panic!("try_add_weight went over the maximum weight");
+        }
+    }
+
+    fn unwrap_test() {
+        let value = crate::Frc::new(String::from("frc"));
+        let shared = value.clone();
+        let value = crate::Frc::try_unwrap(value).expect_err("value is shared");
+        if crate::Frc::unwrap_or_clone(shared) != "frc" {
+            panic!("unwrap_or_clone returned the wrong value");
+        }
+        if crate::Frc::try_unwrap(value).ok().as_deref() != Some("frc") {
+            panic!("try_unwrap failed on a unique value");
+        }
+
+        let value = crate::Frc::new(7_u32);
+        let handles = (0..4)
+            .map(|_| {
+                let v = value.clone();
+                std::thread::spawn(move || crate::Frc::into_inner(v))
+            })
+            .collect::<Vec<_>>();
+        let mut taken = crate::Frc::into_inner(value)
+            .into_iter()
+            .collect::<Vec<_>>();
+        for handle in handles {
+            taken.extend(handle.join().unwrap());
+        }
+        if taken != [7] {
+            panic!("into_inner returned the value {} times", taken.len());
+        }
+
+        let text: crate::Frc<str> = crate::Frc::from("frc");
+        let boxed = crate::Frc::into_box(text).expect("text is not shared");
+        if &*boxed != "frc" {
+            panic!("into_box returned the wrong value: {}", boxed);
+        }
+        let shown = crate::frc_unsize!(crate::Frc::new(String::from("frc")), dyn std::fmt::Display);
+        let Ok(boxed) = crate::Frc::into_box(shown) else {
+            panic!("into_box failed on a unique trait object");
+        };
+        if boxed.to_string() != "frc" {
+            panic!("into_box returned the wrong trait object: {}", boxed);
+        }
+    }
+
+    fn mutation_test() {
+        let mut value = crate::Frc::new(vec![1_u32]);
+        crate::Frc::get_mut(&mut value)
+            .expect("value is not shared")
+            .push(2);
+
+        let shared = value.clone();
+        if crate::Frc::get_mut(&mut value).is_some() {
+            panic!("get_mut succeeded on a shared value");
+        }
+        crate::Frc::make_mut(&mut value).push(3);
+        if *shared != [1, 2] || *value != [1, 2, 3] {
+            panic!("make_mut did not clone on write: {:?} {:?}", shared, value);
+        }
+
+        let weak = crate::Frc::downgrade(&value);
+        if crate::Frc::get_mut(&mut value).is_some() {
+            panic!("get_mut succeeded with a live weak handle");
+        }
+        drop(weak);
+        if crate::Frc::get_mut(&mut value).is_none() {
+            panic!("get_mut failed on a unique value");
+        }
+    }
+
+    fn trait_object_test() {
+        let shown = crate::frc_unsize!(crate::Frc::new(42_u32), dyn std::fmt::Display);
+        let shown_clone = shown.clone();
+        if shown_clone.to_string() != "42" {
+            panic!("trait object mismatch: {}", shown_clone);
+        }
+
+        let any = crate::frc_unsize!(
+            crate::Frc::new(String::from("frc")),
+            dyn std::any::Any + Send + Sync
+        );
+        let any = match any.downcast::<u32>() {
+            Ok(v) => panic!("downcast to the wrong type succeeded: {}", v),
+            Err(any) => any,
+        };
+        match any.downcast::<String>() {
+            Ok(v) if *v == "frc" => {}
+            _ => panic!("downcast to String failed"),
+        }
+    }
+
+    fn unsized_test() {
+        let text: crate::Frc<str> = crate::Frc::from("frc");
+        let text_clone = text.clone();
+        if &*text_clone != "frc" || text != crate::Frc::from(String::from("frc")) {
+            panic!("string payload mismatch: {}", text_clone);
+        }
+
+        let items: crate::Frc<[String]> =
+            crate::Frc::from(vec![String::from("a"), String::from("b")]);
+        let cloned_items: crate::Frc<[String]> = crate::Frc::from(&items[..]);
+        let collected: crate::Frc<[u32]> = (0..4).collect();
+        if cloned_items.len() != 2 || cloned_items[1] != "b" || *collected != [0, 1, 2, 3] {
+            panic!("slice payload mismatch: {:?} {:?}", cloned_items, collected);
+        }
+
+        let boxed: crate::Frc<[u8]> = crate::Frc::from(vec![1_u8, 2, 3].into_boxed_slice());
+        let from_box = std::thread::spawn(move || boxed.iter().map(|v| *v as u32).sum::<u32>())
+            .join()
+            .unwrap();
+        if from_box != 6 {
+            panic!("boxed payload mismatch: {}", from_box);
+        }
+    }
+
+    fn weak_test() {
+        struct Node {
+            me: crate::FrcWeak<Node>,
+            value: u32,
+        }
+
+        let node = crate::Frc::new_cyclic(|me| Node {
+            me: me.clone(),
+            value: 7,
+        });
+        let upgraded = node.me.upgrade().expect("node is still alive");
+        if upgraded.value != 7 {
+            panic!("upgraded value is not 7:: possible corruption")
+        }
+
+        let weak = crate::Frc::downgrade(&node);
+        let cross = std::thread::spawn(move || weak.upgrade().map(|v| v.value))
+            .join()
+            .unwrap();
+        if cross != Some(7) {
+            panic!("cross thread upgrade failed: {:?}", cross);
+        }
+
+        let weak = crate::Frc::downgrade(&node);
+        drop(upgraded);
+        drop(node);
+        if weak.upgrade().is_some() {
+            panic!("upgrade succeeded after the last strong handle was dropped");
+        }
+
+        // A panicking closure leaves the allocation to the weak handles it
+        // cloned, and the last of them frees it.
+        let escaped = std::sync::Mutex::new(None);
+        let built = std::panic::catch_unwind(|| {
+            crate::Frc::<Node>::new_cyclic(|me| {
+                *escaped.lock().unwrap() = Some(me.clone());
+                panic!("building the node failed");
+            })
+        });
+        let escaped = escaped.into_inner().unwrap().expect("weak handle escaped");
+        if built.is_ok() || escaped.upgrade().is_some() {
+            panic!("new_cyclic returned a node whose closure panicked");
+        }
+    }
+
     fn tokio_test() {
         const WORKER_CNT: usize = 8;
-        let mut counter_map = crate::Frc::new(BTreeMap::<u32, AtomicU32>::new());
+        let counter = crate::Frc::new(crate::Counter::new());
         let lock = std::sync::Arc::new(std::sync::Mutex::new(0_u32));
+        let lset_slot = crate::thread::ThreadSlot::new();
         let runtime = tokio::runtime::Builder::new_multi_thread()
             .worker_threads(WORKER_CNT)
             .enable_all()
-            .on_thread_start(|| unsafe {
-                let lset_o = std::boxed::Box::new(tokio::task::LocalSet::new());
-                let lset_raw: *const tokio::task::LocalSet = std::boxed::Box::into_raw(lset_o);
-                let lset_ptr = std::ptr::NonNull::new_unchecked(lset_raw as *mut _).as_ptr();
-                store_local_pointer(0, lset_ptr);
-                initialize_thread_number();
+            .on_thread_start(move || {
+                WORKER_GUARD.with(|guard| *guard.borrow_mut() = Some(crate::thread::register()));
+                lset_slot.set(tokio::task::LocalSet::new());
             })
-            .on_thread_stop(|| unsafe {
-                let lset = get_local_pointer(0) as *mut tokio::task::LocalSet;
-                lset.drop_in_place();
-                uninitialize_thread_number();
+            // Unregistering drops the `LocalSet`.
+            .on_thread_stop(|| {
+                WORKER_GUARD.with(|guard| guard.borrow_mut().take());
             })
             .build();
 
-        for i in 0..WORKER_CNT {
-            counter_map.insert(i as u32, AtomicU32::new(0));
-        }
         if let Ok(runt) = runtime {
             let sv = crate::Frc::new(100);
             for _ in 0..(WORKER_CNT * 100) {
                 let svv = sv.clone();
                 let llock = lock.clone();
-                let cmap = counter_map.clone();
+                let cnt = counter.clone();
                 runt.spawn(async move {
-                    let tn = unsafe { *get_thread_number() };
                     if *svv != 100 {
                         panic!("stored value is not 100:: possible corruption")
                     }
                     match llock.lock() {
                         Ok(v) => {
                             let _ = v.add(1);
-                            if let Some(cnt) = cmap.get(&tn) {
-                                cnt.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                            }
+                            cnt.increment();
                         }
                         Err(e) => {
                             panic!("{}", e);
@@ -196,15 +689,9 @@ mod tests {
             runt.block_on(async {
                 tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
             });
-            let mut totals = 0;
-            for i in 0..WORKER_CNT {
-                if let Some(cnt) = counter_map.get(&(i as u32)) {
-                    let ccnt = cnt.load(std::sync::atomic::Ordering::Relaxed);
-                    totals += ccnt;
-                    println!("{}:{}:{}", i, ccnt, totals);
-                }
-            }
-            if totals != WORKER_CNT as u32 * 100 {
+            let totals = counter.sum();
+            println!("{:?}", counter);
+            if totals != WORKER_CNT as u64 * 100 {
                 panic!("SIZE NOT MATCH expected {}:{}", totals, WORKER_CNT * 100);
             }
         }
@@ -213,166 +700,623 @@ mod tests {
     static SINGLETON_TEST: crate::Singleton<u32> = crate::Singleton::default();
 
     fn singleton_test() {
-        unsafe {
-            crate::initialize_thread_number();
-
-            println!("Setting the counter");
-            let counter_set = crate::Frc::new(std::sync::Mutex::new(BTreeSet::<u32>::new()));
-            // println!("thread main no: {}", *tno);
-            // {
-            //     counter_set.lock().unwrap().insert(*tno);
-            // }
+        let _guard = crate::thread::register();
 
-            let counter_set_1 = counter_set.clone();
-            let counter_set_2 = counter_set.clone();
+        println!("Setting the counter");
+        let counter_set = crate::Frc::new(std::sync::Mutex::new(BTreeSet::<u32>::new()));
+        // println!("thread main no: {}", tno);
+        // {
+        //     counter_set.lock().unwrap().insert(tno);
+        // }
 
-            let handler = std::thread::spawn(move || {
-                crate::initialize_thread_number();
-                if let Some(p) = SINGLETON_TEST.replace(1) {
-                    println!("p0: {}", *p);
-                    counter_set_1.lock().unwrap().insert(*p);
-                } else {
-                    println!("p0: None");
-                }
-                std::thread::sleep(std::time::Duration::from_millis(100));
-                crate::uninitialize_thread_number();
-            });
-            let handler2 = std::thread::spawn(move || {
-                crate::initialize_thread_number();
-                if let Some(p) = SINGLETON_TEST.replace(2) {
-                    println!("p1: {}", *p);
-                    counter_set_2.lock().unwrap().insert(*p);
+        let counter_set_1 = counter_set.clone();
+        let counter_set_2 = counter_set.clone();
+
+        let handler = std::thread::spawn(move || {
+            let _guard = crate::thread::register();
+            if let Some(p) = SINGLETON_TEST.replace(1) {
+                println!("p0: {}", *p);
+                counter_set_1.lock().unwrap().insert(*p);
+            } else {
+                println!("p0: None");
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        });
+        let handler2 = std::thread::spawn(move || {
+            let _guard = crate::thread::register();
+            if let Some(p) = SINGLETON_TEST.replace(2) {
+                println!("p1: {}", *p);
+                counter_set_2.lock().unwrap().insert(*p);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        });
+        handler.join().unwrap();
+        handler2.join().unwrap();
+
+        let p = SINGLETON_TEST.get();
+        {
+            println!("p: {}", *p);
+            if let Ok(mut l) = counter_set.lock() {
+                l.insert(*p);
+                if l.len() != 2 {
+                    panic!("SIZE NOT MATCH expected {}:{}", 2, l.len());
                 }
-                std::thread::sleep(std::time::Duration::from_millis(100));
-                crate::uninitialize_thread_number();
-            });
-            handler.join().unwrap();
-            handler2.join().unwrap();
+            };
+        }
+    }
 
-            let p = SINGLETON_TEST.get();
-            {
-                println!("p: {}", *p);
-                if let Ok(mut l) = counter_set.lock() {
-                    l.insert(*p);
-                    if l.len() != 2 {
-                        panic!("SIZE NOT MATCH expected {}:{}", 2, l.len());
+    static STRESS_TEST: crate::Singleton<Vec<u64>> = crate::Singleton::lazy(|| vec![0; 16]);
+
+    fn singleton_stress_test() {
+        const READERS: usize = 6;
+        const WRITERS: usize = 2;
+        const GETS: usize = 5000;
+        const REPLACES: u64 = 2000;
+
+        std::thread::scope(|s| {
+            for _ in 0..READERS {
+                s.spawn(|| {
+                    for _ in 0..GETS {
+                        let value = STRESS_TEST.get();
+                        if value.iter().any(|v| *v != value[0]) {
+                            panic!("read a corrupted value: {:?}", *value);
+                        }
                     }
-                }
+                });
             }
+            for w in 0..WRITERS as u64 {
+                s.spawn(move || {
+                    for i in 0..REPLACES {
+                        drop(STRESS_TEST.replace(vec![w * REPLACES + i; 16]));
+                    }
+                });
+            }
+        });
+        let value = STRESS_TEST.get();
+        if value.iter().any(|v| *v != value[0]) {
+            panic!("read a corrupted value: {:?}", *value);
+        }
+    }
+
+    static INIT_COUNT: AtomicU32 = AtomicU32::new(0);
+    static LAZY_TEST: crate::Singleton<u32> = crate::Singleton::lazy(|| {
+        INIT_COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        7
+    });
+
+    fn singleton_init_test() {
+        let empty = crate::Singleton::<u32>::default();
+        if empty.try_get().is_some() {
+            panic!("empty singleton returned a value");
+        }
+        if *empty.get_or_init(|| 3) != 3 || *empty.get_or_init(|| 4) != 3 {
+            panic!("get_or_init did not keep the first value");
+        }
 
-            crate::uninitialize_thread_number();
+        if LAZY_TEST.try_get().is_some() {
+            panic!("try_get ran the initialiser");
+        }
+        std::thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    if *LAZY_TEST.get() != 7 {
+                        panic!("lazy singleton returned the wrong value");
+                    }
+                });
+            }
+        });
+        if INIT_COUNT.load(std::sync::atomic::Ordering::Relaxed) != 1 {
+            panic!("lazy singleton was initialised more than once");
         }
     }
 
-    fn thread_counter_test() {
-        unsafe {
-            crate::initialize_thread_number();
-            let tno = crate::get_thread_number();
-            let counter_set = crate::Frc::new(std::sync::Mutex::new(BTreeSet::<u32>::new()));
-            println!("thread main no: {}", *tno);
-            {
-                counter_set.lock().unwrap().insert(*tno);
+    fn singleton_rcu_test() {
+        const THREADS: u64 = 8;
+        const UPDATES: u64 = 1000;
+        let counter = crate::Singleton::new(0_u64);
+        std::thread::scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|| {
+                    for _ in 0..UPDATES {
+                        counter.rcu(|v| v + 1);
+                    }
+                });
             }
+        });
+        if *counter.get() != THREADS * UPDATES {
+            panic!("rcu lost updates: {}", *counter.get());
+        }
 
-            let cset_1 = counter_set.clone();
-            let cset_2 = counter_set.clone();
+        let stale = counter.get();
+        counter.replace(1);
+        if counter.compare_and_swap(&stale, 2) != Err(2) {
+            panic!("compare_and_swap replaced a newer value");
+        }
+        let current = counter.get();
+        if counter.compare_and_swap(&current, 3).map(|prev| *prev) != Ok(1) {
+            panic!("compare_and_swap did not replace the current value");
+        }
 
-            let handler = std::thread::spawn(move || {
-                crate::initialize_thread_number();
-                let tno = crate::get_thread_number();
-                println!("thread sub no: {}", *tno);
-                {
-                    if !cset_1.lock().unwrap().insert(*tno) {
-                        panic!("already inserted thread number : {}", *tno);
-                    }
+        if counter.take().map(|last| *last) != Some(3) || counter.try_get().is_some() {
+            panic!("take did not empty the singleton");
+        }
+        counter.clear();
+        if counter.take().is_some() {
+            panic!("cleared singleton still has a value");
+        }
+        if counter.rcu(|v| v + 1).is_some() || counter.try_get().is_some() {
+            panic!("rcu stored into an empty singleton");
+        }
+
+        // Updates stop once a racing `take` empties the singleton.
+        counter.replace(0);
+        std::thread::scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|| while counter.rcu(|v| v + 1).is_some() {});
+            }
+            counter.take();
+        });
+        if counter.try_get().is_some() {
+            panic!("rcu stored after take emptied the singleton");
+        }
+    }
+
+    fn singleton_watch_test() {
+        let config = std::sync::Arc::new(crate::Singleton::new(String::from("v0")));
+        let start = config.version();
+
+        let waiter = {
+            let config = config.clone();
+            std::thread::spawn(move || config.wait_for_change(start))
+        };
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        config.replace(String::from("v1"));
+        let seen = waiter.join().unwrap();
+        if seen <= start || seen != config.version() {
+            panic!("wait_for_change missed the replace: {} {}", start, seen);
+        }
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let version = runtime.block_on(async {
+            let changed = config.changed();
+            let writer = {
+                let config = config.clone();
+                std::thread::spawn(move || {
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                    config.replace(String::from("v2"));
+                })
+            };
+            let version = changed.await;
+            writer.join().unwrap();
+            version
+        });
+        if version != config.version() || *config.get() != "v2" {
+            panic!("changed resolved before the replace");
+        }
+    }
+
+    fn singleton_local_test() {
+        let config = crate::Singleton::new(1_u32);
+        let first = config.load_local();
+        let shared_weight =
+            || unsafe { first.ptr.as_ref() }.get_weight(std::sync::atomic::Ordering::Relaxed);
+        let before = shared_weight();
+        let second = config.load_local();
+        if !std::ptr::eq(&*first, &*second) || shared_weight() != before {
+            panic!("cached read touched the shared weight");
+        }
+
+        config.replace(2);
+        let current = config.load_local();
+        if *current != 2 {
+            panic!("cache was not refreshed after replace");
+        }
+        let shared_weight =
+            || unsafe { current.ptr.as_ref() }.get_weight(std::sync::atomic::Ordering::Relaxed);
+        let before = shared_weight();
+        // Joined by hand: a scoped thread counts as done before its thread
+        // locals are destroyed.
+        let remote = std::sync::Arc::new(config);
+        let reader = remote.clone();
+        std::thread::spawn(move || {
+            if *reader.load_local() != 2 {
+                panic!("other thread read a stale value");
+            }
+        })
+        .join()
+        .unwrap();
+        if shared_weight() != before {
+            panic!("exited thread kept its cached handle");
+        }
+    }
+
+    fn singleton_tls_destructor_test() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        static CONFIG: crate::Singleton<u32> = crate::Singleton::lazy(|| 1);
+        static UNPINNED: AtomicBool = AtomicBool::new(false);
+
+        struct ReadOnDrop;
+
+        impl Drop for ReadOnDrop {
+            fn drop(&mut self) {
+                UNPINNED.store(crate::epoch::try_pin().is_none(), Ordering::Relaxed);
+                let seen = *CONFIG.get();
+                let previous = CONFIG.replace(seen + 1).map(|v| *v);
+                if previous != Some(seen) || *CONFIG.get() != seen + 1 {
+                    panic!("singleton misbehaved in a thread local destructor");
+                }
+            }
+        }
+
+        thread_local! {
+            static READER: ReadOnDrop = const { ReadOnDrop };
+        }
+
+        let before = *CONFIG.get();
+        std::thread::spawn(|| {
+            // Registered before the lazy registration, so destroyed after it.
+            READER.with(|_| {});
+            crate::registry::current_thread();
+        })
+        .join()
+        .unwrap();
+        if !UNPINNED.load(Ordering::Relaxed) {
+            panic!("the destructor ran while the thread could still pin");
+        }
+        if *CONFIG.get() != before + 1 {
+            panic!("replace from a thread local destructor was lost");
+        }
+    }
+
+    fn singleton_history_test() {
+        let config = crate::Singleton::with_history("good", 3);
+        config.replace("bad");
+        if *config.rollback().unwrap() != "good" || *config.get() != "good" {
+            panic!("rollback did not restore the previous value");
+        }
+        let versions: Vec<u64> = config.history().iter().map(|e| e.version()).collect();
+        if versions != [0] || config.version() != 2 {
+            panic!("unexpected history after rollback: {:?}", versions);
+        }
+        if config.rollback() != Err(crate::NotInHistory) {
+            panic!("rolled back past the oldest value");
+        }
+
+        for value in ["a", "b", "c", "d"] {
+            config.replace(value);
+        }
+        let values: Vec<&str> = config.history().iter().map(|e| **e.value()).collect();
+        if values != ["b", "c", "d"] {
+            panic!("history not bounded: {:?}", values);
+        }
+        let b = config.history()[0].version();
+        if *config.rollback_to(b).unwrap() != "b" || config.history().len() != 1 {
+            panic!("rollback_to did not restore its version");
+        }
+        if config.rollback_to(b + 100).is_ok() {
+            panic!("rolled back to an unknown version");
+        }
+
+        config.take();
+        if *config.rollback().unwrap() != "b" {
+            panic!("rollback did not undo take");
+        }
+    }
+
+    fn singleton_group_test() {
+        static GROUP: crate::SingletonGroup = crate::SingletonGroup::new();
+        static ROUTES: crate::Singleton<u64> = crate::Singleton::lazy(|| 0).in_group(&GROUP);
+        static POOL: crate::Singleton<u64> = crate::Singleton::lazy(|| 0).in_group(&GROUP);
+        static OUTSIDE: crate::Singleton<u64> = crate::Singleton::lazy(|| 0);
+
+        // Staged updates are not visible before the commit, and dropped
+        // without one.
+        let mut tx = GROUP.transaction();
+        tx.replace(&ROUTES, 7).replace(&POOL, 7);
+        if *ROUTES.get() != 0 {
+            panic!("staged update stored before commit");
+        }
+        drop(tx);
+        if *ROUTES.get() != 0 {
+            panic!("dropped transaction stored its updates");
+        }
+        let outside = std::panic::catch_unwind(|| {
+            GROUP.transaction().replace(&OUTSIDE, 1);
+        });
+        if outside.is_ok() {
+            panic!("transaction accepted a singleton outside its group");
+        }
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1..=1000 {
+                    let mut tx = GROUP.transaction();
+                    tx.replace(&ROUTES, i).replace(&POOL, i);
+                    tx.commit();
                 }
-                std::thread::sleep(std::time::Duration::from_millis(100));
-                crate::uninitialize_thread_number();
             });
-            let handler2 = std::thread::spawn(move || {
-                crate::initialize_thread_number();
-                let tno = crate::get_thread_number();
-                println!("thread sub no: {}", *tno);
-                {
-                    if !cset_2.lock().unwrap().insert(*tno) {
-                        panic!("already inserted thread number : {}", *tno);
-                    }
+            // Direct stores on a member are writes of the group too.
+            s.spawn(|| {
+                for _ in 0..1000 {
+                    ROUTES.rcu(|routes| *routes);
                 }
-                std::thread::sleep(std::time::Duration::from_millis(100));
-                crate::uninitialize_thread_number();
             });
-            handler.join().unwrap();
-            handler2.join().unwrap();
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        let (routes, pool) = GROUP.snapshot(|| (ROUTES.get(), POOL.get()));
+                        if *routes != *pool {
+                            panic!("torn snapshot: {} and {}", *routes, *pool);
+                        }
+                    }
+                });
+            }
+        });
+        if *GROUP.snapshot(|| POOL.get()) != 1000 {
+            panic!("last transaction not published");
+        }
+    }
+
+    #[cfg(feature = "reload")]
+    fn reloadable_singleton_test() {
+        use std::time::{Duration, SystemTime};
 
+        let dir = std::env::temp_dir().join(format!("frc-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Files are rewritten with an explicit modification time, so the
+        // test does not depend on the resolution of the file system.
+        let write = |path: &std::path::Path, text: &str, secs: u64| {
+            std::fs::write(path, text).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+                .unwrap();
+        };
+
+        let json = dir.join("config.json");
+        write(&json, r#"{"port": 80}"#, 1);
+        let config =
+            crate::ReloadableSingleton::<std::collections::BTreeMap<String, u16>>::load(&json)
+                .unwrap();
+        if config.get()["port"] != 80 || config.reload_if_modified().unwrap() {
+            panic!("json file not loaded");
+        }
+        write(&json, r#"{"port": "#, 2);
+        if config.reload_if_modified().is_ok() || config.get()["port"] != 80 {
+            panic!("broken file replaced the value");
+        }
+        write(&json, r#"{"port": 81}"#, 3);
+        config.reload().unwrap();
+        if config.get()["port"] != 81 {
+            panic!("json file not reloaded");
+        }
+        std::fs::remove_file(&json).unwrap();
+        if config.reload_if_modified().is_ok() || config.reload_if_modified().is_ok() {
+            panic!("checked a removed file");
+        }
+        write(&json, r#"{"port": 82}"#, 4);
+        if !config.reload_if_modified().unwrap() || config.get()["port"] != 82 {
+            panic!("restored file not reloaded");
+        }
+
+        let toml = dir.join("config.toml");
+        write(&toml, "port = 1", 1);
+        let config = std::sync::Arc::new(
+            crate::ReloadableSingleton::<std::collections::BTreeMap<String, u16>>::load(&toml)
+                .unwrap(),
+        );
+        let watcher = config.watch(Duration::from_millis(5));
+        let version = config.version();
+        write(&toml, "port = 2", 2);
+        config.wait_for_change(version);
+        drop(watcher);
+        if config.get()["port"] != 2 {
+            panic!("watcher did not reload the toml file");
+        }
+
+        if crate::ReloadableSingleton::<u32>::load(dir.join("config.ini")).is_ok() {
+            panic!("loaded a file of unknown format");
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn thread_counter_test() {
+        let _guard = crate::thread::register();
+        let tno = crate::thread::current_thread_number().unwrap();
+        let counter_set = crate::Frc::new(std::sync::Mutex::new(BTreeSet::<u32>::new()));
+        println!("thread main no: {}", tno);
+        {
+            counter_set.lock().unwrap().insert(tno);
+        }
+
+        let cset_1 = counter_set.clone();
+        let cset_2 = counter_set.clone();
+
+        let handler = std::thread::spawn(move || {
+            let _guard = crate::thread::register();
+            let tno = crate::thread::current_thread_number().unwrap();
+            println!("thread sub no: {}", tno);
             {
-                counter_set.lock().unwrap().clear();
+                if !cset_1.lock().unwrap().insert(tno) {
+                    panic!("already inserted thread number : {}", tno);
+                }
             }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        });
+        let handler2 = std::thread::spawn(move || {
+            let _guard = crate::thread::register();
+            let tno = crate::thread::current_thread_number().unwrap();
+            println!("thread sub no: {}", tno);
+            {
+                if !cset_2.lock().unwrap().insert(tno) {
+                    panic!("already inserted thread number : {}", tno);
+                }
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        });
+        handler.join().unwrap();
+        handler2.join().unwrap();
 
-            let cset_1 = counter_set.clone();
-            let cset_2 = counter_set.clone();
-            let cset_3 = counter_set.clone();
+        {
+            counter_set.lock().unwrap().clear();
+        }
 
-            let handler = std::thread::spawn(move || {
-                crate::initialize_thread_number();
-                let tno = crate::get_thread_number();
-                println!("thread sub no: {}", *tno);
-                {
-                    if !cset_1.lock().unwrap().insert(*tno) {
-                        panic!("already inserted thread number : {}", *tno);
-                    }
+        let cset_1 = counter_set.clone();
+        let cset_2 = counter_set.clone();
+        let cset_3 = counter_set.clone();
+
+        let handler = std::thread::spawn(move || {
+            let _guard = crate::thread::register();
+            let tno = crate::thread::current_thread_number().unwrap();
+            println!("thread sub no: {}", tno);
+            {
+                if !cset_1.lock().unwrap().insert(tno) {
+                    panic!("already inserted thread number : {}", tno);
                 }
-                std::thread::sleep(std::time::Duration::from_millis(100));
-                crate::uninitialize_thread_number();
-            });
-            let handler2 = std::thread::spawn(move || {
-                crate::initialize_thread_number();
-                let tno = crate::get_thread_number();
-                println!("thread sub no: {}", *tno);
-                {
-                    if !cset_2.lock().unwrap().insert(*tno) {
-                        panic!("already inserted thread number : {}", *tno);
-                    }
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        });
+        let handler2 = std::thread::spawn(move || {
+            let _guard = crate::thread::register();
+            let tno = crate::thread::current_thread_number().unwrap();
+            println!("thread sub no: {}", tno);
+            {
+                if !cset_2.lock().unwrap().insert(tno) {
+                    panic!("already inserted thread number : {}", tno);
                 }
-                std::thread::sleep(std::time::Duration::from_millis(100));
-                crate::uninitialize_thread_number();
-            });
-            let handler3 = std::thread::spawn(move || {
-                crate::initialize_thread_number();
-                let tno = crate::get_thread_number();
-                println!("thread sub no: {}", *tno);
-                {
-                    if !cset_3.lock().unwrap().insert(*tno) {
-                        panic!("already inserted thread number : {}", *tno);
-                    }
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        });
+        let handler3 = std::thread::spawn(move || {
+            let _guard = crate::thread::register();
+            let tno = crate::thread::current_thread_number().unwrap();
+            println!("thread sub no: {}", tno);
+            {
+                if !cset_3.lock().unwrap().insert(tno) {
+                    panic!("already inserted thread number : {}", tno);
                 }
-                std::thread::sleep(std::time::Duration::from_millis(100));
-                crate::uninitialize_thread_number();
-            });
-            handler.join().unwrap();
-            handler2.join().unwrap();
-            handler3.join().unwrap();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        });
+        handler.join().unwrap();
+        handler2.join().unwrap();
+        handler3.join().unwrap();
 
+        {
+            counter_set.lock().unwrap().clear();
+        }
+
+        let cset_1 = counter_set;
+
+        let handler = std::thread::spawn(move || {
+            let _guard = crate::thread::register();
+            let tno = crate::thread::current_thread_number().unwrap();
+            println!("thread sub no: {}", tno);
             {
-                counter_set.lock().unwrap().clear();
+                if !cset_1.lock().unwrap().insert(tno) {
+                    panic!("already inserted thread number : {}", tno);
+                }
             }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        });
+        handler.join().unwrap();
+    }
+}
 
-            let cset_1 = counter_set;
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use loom::sync::Arc;
+    use loom::thread;
 
-            let handler = std::thread::spawn(move || {
-                crate::initialize_thread_number();
-                let tno = crate::get_thread_number();
-                println!("thread sub no: {}", *tno);
-                {
-                    if !cset_1.lock().unwrap().insert(*tno) {
-                        panic!("already inserted thread number : {}", *tno);
-                    }
-                }
-                std::thread::sleep(std::time::Duration::from_millis(100));
-                crate::uninitialize_thread_number();
+    #[test]
+    fn clone_and_drop_across_threads() {
+        loom::model(|| {
+            let value = crate::Frc::new(String::from("frc"));
+            let sent = value.clone();
+            let handle = thread::spawn(move || {
+                let local = sent.clone();
+                drop(sent);
+                local.len()
             });
-            handler.join().unwrap();
+            let local = value.clone();
+            drop(value);
+            assert_eq!(handle.join().unwrap(), local.len());
+        });
+    }
 
-            crate::uninitialize_thread_number();
+    #[test]
+    fn singleton_group_snapshot_is_consistent() {
+        loom::lazy_static! {
+            static ref GROUP: crate::SingletonGroup = crate::SingletonGroup::new();
+            static ref A: crate::Singleton<u32> = crate::Singleton::new(1).in_group(&GROUP);
+            static ref B: crate::Singleton<u32> = crate::Singleton::new(1).in_group(&GROUP);
         }
+
+        loom::model(|| {
+            let writer = thread::spawn(|| {
+                let mut tx = GROUP.transaction();
+                tx.replace(&A, 2).replace(&B, 2);
+                tx.commit();
+            });
+            let (seen_a, seen_b) = GROUP.snapshot(|| (*A.get(), *B.get()));
+            assert_eq!(seen_a, seen_b);
+            writer.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn singleton_replace_and_get() {
+        loom::model(|| {
+            let singleton = Arc::new(crate::Singleton::new(1_u32));
+            let writer = {
+                let singleton = singleton.clone();
+                thread::spawn(move || singleton.replace(2))
+            };
+            let seen = *singleton.get();
+            let previous = writer.join().unwrap().unwrap();
+            assert!(seen == 1 || seen == 2);
+            assert_eq!(*previous, 1);
+        });
+    }
+
+    #[test]
+    fn singleton_get_races_replace() {
+        loom::model(|| {
+            let singleton = Arc::new(crate::Singleton::new(1_u32));
+            let writer = {
+                let singleton = singleton.clone();
+                thread::spawn(move || {
+                    drop(singleton.replace(2));
+                    // Gives the deferred release a chance to run under the
+                    // racing `get`.
+                    for _ in 0..2 {
+                        crate::epoch::pin().flush();
+                    }
+                })
+            };
+            let seen = *singleton.get();
+            writer.join().unwrap();
+            assert!(seen == 1 || seen == 2);
+        });
+    }
+
+    #[test]
+    fn singleton_get_or_init_runs_once() {
+        loom::model(|| {
+            let singleton = Arc::new(crate::Singleton::default());
+            let racer = {
+                let singleton = singleton.clone();
+                thread::spawn(move || *singleton.get_or_init(|| 1_u32))
+            };
+            let seen = *singleton.get_or_init(|| 2_u32);
+            assert_eq!(racer.join().unwrap(), seen);
+        });
     }
 }
@@ -0,0 +1,209 @@
+// Per thread storage indexed by thread number. Thread numbers are small and
+// dense, so the values live in buckets of doubling size: bucket `b` holds
+// the numbers `2^b - 1 .. 2^(b+1) - 1` and is allocated on first use.
+use crate::registry::UNREGISTERED;
+use crate::sync::{
+    atomic::{self, AtomicBool, AtomicPtr},
+    const_unless_loom,
+};
+use std::{cell::UnsafeCell, fmt, marker::PhantomData, mem::MaybeUninit, ptr};
+
+const BUCKETS: usize = u32::BITS as usize;
+
+struct Entry<T> {
+    present: AtomicBool,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+#[cfg(not(loom))]
+const fn empty_buckets<T>() -> [AtomicPtr<Entry<T>>; BUCKETS] {
+    [const { AtomicPtr::new(ptr::null_mut()) }; BUCKETS]
+}
+
+// An array of loom atomics cannot be built by repeating a const.
+#[cfg(loom)]
+fn empty_buckets<T>() -> [AtomicPtr<Entry<T>>; BUCKETS] {
+    std::array::from_fn(|_| AtomicPtr::new(ptr::null_mut()))
+}
+
+/// A value per thread, which any thread can enumerate.
+///
+/// The value of the current thread is created on first use by `get_or` and
+/// stays until the `ThreadLocalVec` is dropped. Thread numbers are reused,
+/// so a thread may find the value left by the previous holder of its number.
+pub struct ThreadLocalVec<T> {
+    buckets: [AtomicPtr<Entry<T>>; BUCKETS],
+    _marker: PhantomData<T>,
+}
+
+// Only the thread holding a number writes its entry. `T: Sync` because a
+// thread can unregister while still borrowing its value, and the next
+// holder of the number then borrows it too.
+unsafe impl<T: Send> Send for ThreadLocalVec<T> {}
+unsafe impl<T: Send + Sync> Sync for ThreadLocalVec<T> {}
+
+impl<T> ThreadLocalVec<T> {
+    const_unless_loom! {
+        pub fn new() -> ThreadLocalVec<T> {
+            ThreadLocalVec {
+                buckets: empty_buckets(),
+                _marker: PhantomData,
+            }
+        }
+    }
+
+    /// Returns the value of the current thread, creating it with `create`
+    /// if there is none. The thread is registered if it is not yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called by a thread whose thread locals are being
+    /// destroyed, since it cannot be given a thread number anymore.
+    pub fn get_or(&self, create: impl FnOnce() -> T) -> &T {
+        let entry = self.entry(current_number());
+        if entry.present.load(atomic::Ordering::Acquire) {
+            return unsafe { (*entry.value.get()).assume_init_ref() };
+        }
+        let value = create();
+        // `create` may have filled the entry through this same call.
+        if !entry.present.load(atomic::Ordering::Acquire) {
+            unsafe { (*entry.value.get()).write(value) };
+            entry.present.store(true, atomic::Ordering::Release);
+        }
+        unsafe { (*entry.value.get()).assume_init_ref() }
+    }
+
+    /// Returns the value of the current thread, if it has one.
+    pub fn get(&self) -> Option<&T> {
+        let no = crate::thread::current_thread_number()?;
+        let (bucket, index) = locate(no);
+        let entries = self.buckets[bucket].load(atomic::Ordering::Acquire);
+        if entries.is_null() {
+            return None;
+        }
+        let entry = unsafe { &*entries.add(index) };
+        if entry.present.load(atomic::Ordering::Acquire) {
+            Some(unsafe { (*entry.value.get()).assume_init_ref() })
+        } else {
+            None
+        }
+    }
+
+    /// Iterates over the values of every thread.
+    pub fn iter(&self) -> impl Iterator<Item = &T>
+    where
+        T: Sync,
+    {
+        self.entries()
+            .filter(|entry| entry.present.load(atomic::Ordering::Acquire))
+            .map(|entry| unsafe { (*entry.value.get()).assume_init_ref() })
+    }
+
+    /// Iterates mutably over the values of every thread.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.entries()
+            .filter(|entry| entry.present.load(atomic::Ordering::Relaxed))
+            .map(|entry| unsafe { (*entry.value.get()).assume_init_mut() })
+    }
+
+    // The entry of thread number `no`, allocating its bucket if needed.
+    fn entry(&self, no: u32) -> &Entry<T> {
+        let (bucket, index) = locate(no);
+        let slot = &self.buckets[bucket];
+        let mut entries = slot.load(atomic::Ordering::Acquire);
+        if entries.is_null() {
+            let new = allocate_bucket::<T>(bucket_len(bucket));
+            entries = match slot.compare_exchange(
+                ptr::null_mut(),
+                new,
+                atomic::Ordering::AcqRel,
+                atomic::Ordering::Acquire,
+            ) {
+                Ok(_) => new,
+                Err(existing) => {
+                    unsafe { free_bucket(new, bucket_len(bucket)) };
+                    existing
+                }
+            };
+        }
+        unsafe { &*entries.add(index) }
+    }
+
+    // Every entry of every allocated bucket.
+    fn entries(&self) -> impl Iterator<Item = &Entry<T>> {
+        self.buckets.iter().enumerate().flat_map(|(bucket, slot)| {
+            let entries = slot.load(atomic::Ordering::Acquire);
+            let len = if entries.is_null() {
+                0
+            } else {
+                bucket_len(bucket)
+            };
+            (0..len).map(move |index| unsafe { &*entries.add(index) })
+        })
+    }
+}
+
+impl<T> Default for ThreadLocalVec<T> {
+    fn default() -> Self {
+        ThreadLocalVec::new()
+    }
+}
+
+impl<T> Drop for ThreadLocalVec<T> {
+    fn drop(&mut self) {
+        for (bucket, slot) in self.buckets.iter().enumerate() {
+            let entries = slot.load(atomic::Ordering::Acquire);
+            if entries.is_null() {
+                continue;
+            }
+            for index in 0..bucket_len(bucket) {
+                let entry = unsafe { &mut *entries.add(index) };
+                if entry.present.load(atomic::Ordering::Relaxed) {
+                    unsafe { entry.value.get_mut().assume_init_drop() };
+                }
+            }
+            unsafe { free_bucket(entries, bucket_len(bucket)) };
+        }
+    }
+}
+
+impl<T: fmt::Debug + Sync> fmt::Debug for ThreadLocalVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+fn current_number() -> u32 {
+    let no = crate::registry::current_thread().number();
+    if no == UNREGISTERED {
+        panic!("ThreadLocalVec used while the thread locals of its thread are being destroyed");
+    }
+    no
+}
+
+// The bucket and the index in it of thread number `no`.
+#[inline]
+fn locate(no: u32) -> (usize, usize) {
+    let n = no as usize + 1;
+    let bucket = (usize::BITS - 1 - n.leading_zeros()) as usize;
+    (bucket, n - (1 << bucket))
+}
+
+#[inline]
+fn bucket_len(bucket: usize) -> usize {
+    1 << bucket
+}
+
+fn allocate_bucket<T>(len: usize) -> *mut Entry<T> {
+    let entries: Box<[Entry<T>]> = (0..len)
+        .map(|_| Entry {
+            present: AtomicBool::new(false),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        })
+        .collect();
+    Box::into_raw(entries) as *mut Entry<T>
+}
+
+unsafe fn free_bucket<T>(entries: *mut Entry<T>, len: usize) {
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(entries, len)));
+}
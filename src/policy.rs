@@ -0,0 +1,55 @@
+/// Decides how much weight the handles of an `Frc` carry.
+///
+/// A handle created from the shared counter (`Frc::new`, a clone on another
+/// thread, an upgrade) takes `INITIAL_WEIGHT`. A clone on the owning thread
+/// takes `weight >> SPLIT_SHIFT` from its source without touching the shared
+/// counter, and once the source can no longer be split the pair is topped up
+/// to `REFILL_WEIGHT` from the shared counter first.
+///
+/// `INITIAL_WEIGHT` and `REFILL_WEIGHT >> SPLIT_SHIFT` must be non-zero and
+/// `SPLIT_SHIFT` must be between 1 and `usize::BITS - 1`. Code creating a
+/// handle with a policy breaking these fails to compile.
+pub trait WeightPolicy: 'static {
+    const INITIAL_WEIGHT: usize;
+    const SPLIT_SHIFT: u32 = 1;
+    const REFILL_WEIGHT: usize = Self::INITIAL_WEIGHT << 1;
+}
+
+/// The policy used unless an `Frc` names another one: handles start with
+/// `1 << (usize::BITS / 4)`, that is `1 << 16` on 64-bit targets and `1 << 8`
+/// on 32-bit ones, same-thread clones halve the weight and refill to twice
+/// the initial weight.
+///
+/// Scaling with the pointer width keeps room for `2^47` handles taking the
+/// initial weight on 64-bit targets and `2^23` on 32-bit ones.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultWeight;
+
+impl WeightPolicy for DefaultWeight {
+    const INITIAL_WEIGHT: usize = 1 << (usize::BITS / 4);
+    const SPLIT_SHIFT: u32 = 1;
+    const REFILL_WEIGHT: usize = Self::INITIAL_WEIGHT << 1;
+}
+
+// The `policy` field of every handle. Creating one rejects a broken policy
+// when the first handle using it is created, whichever constructor makes
+// that handle.
+pub(crate) struct PolicyMarker<P>(std::marker::PhantomData<P>);
+
+impl<P: WeightPolicy> PolicyMarker<P> {
+    const OK: () = assert!(
+        P::INITIAL_WEIGHT > 0
+            && P::INITIAL_WEIGHT <= crate::inner::MAX_WEIGHT
+            && P::SPLIT_SHIFT > 0
+            && P::SPLIT_SHIFT < usize::BITS
+            && (P::REFILL_WEIGHT >> P::SPLIT_SHIFT) > 0,
+        "invalid WeightPolicy"
+    );
+
+    #[inline]
+    pub(crate) const fn new() -> PolicyMarker<P> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::OK;
+        PolicyMarker(std::marker::PhantomData)
+    }
+}
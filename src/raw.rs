@@ -0,0 +1,78 @@
+//! Raw access to the thread registry.
+//!
+//! Everything here is `unsafe`: registrations must be paired by hand and
+//! thread local pointers are neither bounds checked nor dropped. Prefer
+//! `frc::thread::register`, `frc::thread::current_thread_number` and
+//! `frc::thread::ThreadSlot`.
+use crate::registry::{Backend, Registry};
+use crate::thread::RegistrationError;
+use std::ffi::c_void;
+
+/// The thread number read through `get_thread_number` by a thread that is
+/// not registered. No registered thread is given it.
+pub const UNREGISTERED: u32 = crate::registry::UNREGISTERED;
+
+/// # Safety
+///
+/// The returned pointer is only valid on the calling thread, for as long as
+/// the thread is alive.
+#[inline]
+pub unsafe fn get_thread_number() -> *mut u32 {
+    Backend::get_thread_number()
+}
+
+/// # Safety
+///
+/// Must be paired with `uninitialize_thread_number` on the same thread.
+///
+/// # Errors
+///
+/// Fails without changing the registration if the thread is already
+/// registered.
+#[inline]
+pub unsafe fn initialize_thread_number() -> Result<(), RegistrationError> {
+    if Backend::initialize_thread_number() {
+        Ok(())
+    } else {
+        Err(RegistrationError::AlreadyRegistered)
+    }
+}
+
+/// # Safety
+///
+/// Must be paired with `initialize_thread_number` on the same thread. A
+/// registration held by a `ThreadGuard`, or made on first use, belongs to
+/// that guard or to the thread locals and must not be undone here.
+///
+/// Handles created on this thread stay usable: the generation of the number
+/// tells this thread apart from its next holder.
+///
+/// # Errors
+///
+/// Fails if the thread is not registered.
+#[inline]
+pub unsafe fn uninitialize_thread_number() -> Result<(), RegistrationError> {
+    if crate::registry::unregister() {
+        Ok(())
+    } else {
+        Err(RegistrationError::NotRegistered)
+    }
+}
+
+/// # Safety
+///
+/// `pos` must be below 1024.
+#[deprecated(note = "use `frc::thread::ThreadSlot`, which is bounds checked and drops its values")]
+#[inline]
+pub unsafe fn store_local_pointer(pos: i32, ptr: *mut c_void) {
+    Backend::store_local_pointer(pos, ptr)
+}
+
+/// # Safety
+///
+/// `pos` must be below 1024.
+#[deprecated(note = "use `frc::thread::ThreadSlot`, which is bounds checked and drops its values")]
+#[inline]
+pub unsafe fn get_local_pointer(pos: i32) -> *mut c_void {
+    Backend::get_local_pointer(pos)
+}
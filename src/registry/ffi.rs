@@ -0,0 +1,52 @@
+// The C++ registry in `src_c/th.cc`.
+use super::Registry;
+use std::ffi::c_void;
+
+extern "C" {
+    fn get_thread_number() -> *mut u32;
+    fn get_thread_generation() -> u32;
+    fn is_thread_registered() -> bool;
+    fn initialize_thread_number() -> bool;
+    fn uninitialize_thread_number() -> bool;
+    fn store_local_pointer(pos: i32, ptr: *mut c_void);
+    fn get_local_pointer(pos: i32) -> *mut c_void;
+}
+
+pub(crate) struct CxxRegistry;
+
+impl Registry for CxxRegistry {
+    #[inline]
+    unsafe fn initialize_thread_number() -> bool {
+        initialize_thread_number()
+    }
+
+    #[inline]
+    unsafe fn uninitialize_thread_number() -> bool {
+        uninitialize_thread_number()
+    }
+
+    #[inline]
+    unsafe fn get_thread_number() -> *mut u32 {
+        get_thread_number()
+    }
+
+    #[inline]
+    unsafe fn get_thread_generation() -> u32 {
+        get_thread_generation()
+    }
+
+    #[inline]
+    fn is_thread_registered() -> bool {
+        unsafe { is_thread_registered() }
+    }
+
+    #[inline]
+    unsafe fn store_local_pointer(pos: i32, ptr: *mut c_void) {
+        store_local_pointer(pos, ptr)
+    }
+
+    #[inline]
+    unsafe fn get_local_pointer(pos: i32) -> *mut c_void {
+        get_local_pointer(pos)
+    }
+}
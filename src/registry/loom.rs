@@ -0,0 +1,49 @@
+// Loom runs every model thread on the same OS thread, so neither of the
+// other registries can tell them apart. Number the loom threads instead.
+use super::Registry;
+use std::{
+    cell::UnsafeCell,
+    ffi::c_void,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+// Reset for every execution, so that the numbers stay small.
+loom::lazy_static! {
+    static ref NEXT: AtomicU32 = AtomicU32::new(0);
+}
+
+loom::thread_local! {
+    static TNUM: UnsafeCell<u32> = UnsafeCell::new(NEXT.fetch_add(1, Ordering::Relaxed));
+}
+
+pub(crate) struct LoomRegistry;
+
+impl Registry for LoomRegistry {
+    unsafe fn initialize_thread_number() -> bool {
+        false
+    }
+
+    unsafe fn uninitialize_thread_number() -> bool {
+        false
+    }
+
+    unsafe fn get_thread_number() -> *mut u32 {
+        TNUM.with(|tnum| tnum.get())
+    }
+
+    unsafe fn get_thread_generation() -> u32 {
+        0
+    }
+
+    fn is_thread_registered() -> bool {
+        true
+    }
+
+    // Thread local pointers are not modelled: stores are dropped and every
+    // slot reads as null, like one that was never stored.
+    unsafe fn store_local_pointer(_pos: i32, _ptr: *mut c_void) {}
+
+    unsafe fn get_local_pointer(_pos: i32) -> *mut c_void {
+        std::ptr::null_mut()
+    }
+}
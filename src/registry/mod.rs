@@ -0,0 +1,123 @@
+// Thread numbering backends. Every backend hands out small, dense thread
+// numbers, giving a released number back to the next thread that asks for
+// one (smallest first), and keeps a small array of thread local pointers.
+use std::{cell::Cell, ffi::c_void};
+
+#[cfg(all(not(loom), not(feature = "native-registry")))]
+mod ffi;
+#[cfg(loom)]
+mod loom;
+#[cfg(all(not(loom), feature = "native-registry"))]
+mod native;
+
+#[cfg(all(not(loom), not(feature = "native-registry")))]
+pub(crate) use ffi::CxxRegistry as Backend;
+#[cfg(loom)]
+pub(crate) use loom::LoomRegistry as Backend;
+#[cfg(all(not(loom), feature = "native-registry"))]
+pub(crate) use native::NativeRegistry as Backend;
+
+pub(crate) trait Registry {
+    // Takes a thread number for the current thread, failing if it already
+    // holds one.
+    unsafe fn initialize_thread_number() -> bool;
+    // Gives the number of the current thread back for reuse, failing if it
+    // holds none.
+    unsafe fn uninitialize_thread_number() -> bool;
+    // Points at the number of the current thread.
+    unsafe fn get_thread_number() -> *mut u32;
+    // The generation of the current registration. Every registration gets a
+    // new one, so a recycled number is told apart from its previous holder.
+    unsafe fn get_thread_generation() -> u32;
+    // Whether the current thread holds a number.
+    fn is_thread_registered() -> bool;
+    unsafe fn store_local_pointer(pos: i32, ptr: *mut c_void);
+    unsafe fn get_local_pointer(pos: i32) -> *mut c_void;
+}
+
+// The thread number read by an unregistered thread. No registered thread
+// is ever given it.
+pub(crate) const UNREGISTERED: u32 = u32::MAX;
+
+// The registration a handle was created under.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ThreadToken {
+    number: u32,
+    generation: u32,
+}
+
+impl ThreadToken {
+    // Whether a handle created under `self` can split its weight on the
+    // thread holding `current`.
+    #[inline]
+    pub(crate) fn is_current(self, current: ThreadToken) -> bool {
+        self == current && current.number != UNREGISTERED
+    }
+
+    pub(crate) fn number(self) -> u32 {
+        self.number
+    }
+}
+
+// Releases the number of a thread registered by `current_thread` when the
+// thread exits.
+struct LazyRegistration(Cell<bool>);
+
+impl Drop for LazyRegistration {
+    fn drop(&mut self) {
+        if self.0.get() {
+            unregister();
+        }
+    }
+}
+
+thread_local! {
+    static LAZY: LazyRegistration = const { LazyRegistration(Cell::new(false)) };
+}
+
+// The registration of the current thread, as captured by `Frc` handles.
+// Threads are registered on first use, except while their thread locals are
+// being destroyed, where the token holds `UNREGISTERED`.
+#[inline]
+pub(crate) fn current_thread() -> ThreadToken {
+    let mut number = unsafe { *Backend::get_thread_number() };
+    if number == UNREGISTERED {
+        register_lazily();
+        number = unsafe { *Backend::get_thread_number() };
+    }
+    ThreadToken {
+        number,
+        generation: unsafe { Backend::get_thread_generation() },
+    }
+}
+
+#[cold]
+fn register_lazily() {
+    let _ = LAZY.try_with(|lazy| {
+        if unsafe { Backend::initialize_thread_number() } {
+            lazy.0.set(true);
+        }
+    });
+}
+
+// Releases the number of the current thread and drops its `ThreadSlot`
+// values, or arranges for it once the thread unpins. Returns false if the
+// thread was not registered.
+pub(crate) fn unregister() -> bool {
+    // A pinned thread keeps its number, and so its epoch slot, until it
+    // unpins.
+    if crate::epoch::defer_unregister() {
+        return true;
+    }
+    let released = unsafe { Backend::uninitialize_thread_number() };
+    if released {
+        crate::slot::release_thread();
+    }
+    released
+}
+
+// Hands a lazy registration of the current thread over to the caller, who
+// becomes responsible for releasing it.
+pub(crate) fn adopt_lazy_registration() -> bool {
+    LAZY.try_with(|lazy| lazy.0.replace(false)).unwrap_or(false)
+}
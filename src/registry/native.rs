@@ -0,0 +1,81 @@
+// A pure Rust registry with the same numbering as `src_c/th.cc`, for builds
+// that cannot link C++ (Miri, cross compiling, static musl).
+use super::{Registry, UNREGISTERED};
+use std::{
+    cell::Cell,
+    collections::BTreeSet,
+    ffi::c_void,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+const TLOCAL_ARRAY_LEN: usize = 1024;
+
+static TINC: AtomicU32 = AtomicU32::new(0);
+static TSET: Mutex<BTreeSet<u32>> = Mutex::new(BTreeSet::new());
+static TGEN: AtomicU32 = AtomicU32::new(0);
+
+thread_local! {
+    static IS_INITIALIZED: Cell<bool> = const { Cell::new(false) };
+    static TNUM: Cell<u32> = const { Cell::new(UNREGISTERED) };
+    static GEN: Cell<u32> = const { Cell::new(0) };
+    static TPTR_ARRAY: [Cell<*mut c_void>; TLOCAL_ARRAY_LEN] =
+        const { [const { Cell::new(std::ptr::null_mut()) }; TLOCAL_ARRAY_LEN] };
+}
+
+pub(crate) struct NativeRegistry;
+
+impl Registry for NativeRegistry {
+    unsafe fn initialize_thread_number() -> bool {
+        if IS_INITIALIZED.with(Cell::get) {
+            return false;
+        }
+        let recycled = {
+            let mut tset = TSET.lock().unwrap_or_else(|e| e.into_inner());
+            tset.pop_first()
+        };
+        let tnum = recycled.unwrap_or_else(|| TINC.fetch_add(1, Ordering::Relaxed));
+        TNUM.with(|n| n.set(tnum));
+        GEN.with(|g| g.set(TGEN.fetch_add(1, Ordering::Relaxed)));
+        IS_INITIALIZED.with(|i| i.set(true));
+        true
+    }
+
+    unsafe fn uninitialize_thread_number() -> bool {
+        if !IS_INITIALIZED.with(Cell::get) {
+            return false;
+        }
+        let tnum = TNUM.with(Cell::get);
+        TSET.lock().unwrap_or_else(|e| e.into_inner()).insert(tnum);
+        TNUM.with(|n| n.set(UNREGISTERED));
+        IS_INITIALIZED.with(|i| i.set(false));
+        true
+    }
+
+    #[inline]
+    unsafe fn get_thread_number() -> *mut u32 {
+        TNUM.with(Cell::as_ptr)
+    }
+
+    #[inline]
+    unsafe fn get_thread_generation() -> u32 {
+        GEN.with(Cell::get)
+    }
+
+    #[inline]
+    fn is_thread_registered() -> bool {
+        IS_INITIALIZED.with(Cell::get)
+    }
+
+    #[inline]
+    unsafe fn store_local_pointer(pos: i32, ptr: *mut c_void) {
+        TPTR_ARRAY.with(|arr| arr[pos as usize].set(ptr))
+    }
+
+    #[inline]
+    unsafe fn get_local_pointer(pos: i32) -> *mut c_void {
+        TPTR_ARRAY.with(|arr| arr[pos as usize].get())
+    }
+}
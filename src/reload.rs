@@ -0,0 +1,241 @@
+// A singleton loaded from a file. The watcher thread sleeps on std locks,
+// since it is not model checked.
+use crate::Singleton;
+use serde::de::DeserializeOwned;
+use std::{
+    fmt, fs, io,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, SystemTime},
+};
+
+/// The format of a file backing a `ReloadableSingleton`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// The format named by the extension of `path`, `.json` or `.toml`.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()? {
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    fn parse<T: DeserializeOwned>(self, text: &str) -> Result<T, ReloadError> {
+        match self {
+            Format::Json => serde_json::from_str(text).map_err(ReloadError::Json),
+            Format::Toml => toml::from_str(text).map_err(ReloadError::Toml),
+        }
+    }
+}
+
+/// The error returned when a `ReloadableSingleton` cannot load its file.
+#[derive(Debug)]
+pub enum ReloadError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    /// The extension of the path names no known format.
+    UnknownFormat(PathBuf),
+}
+
+impl fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReloadError::Io(e) => write!(f, "cannot read file: {}", e),
+            ReloadError::Json(e) => write!(f, "invalid JSON: {}", e),
+            ReloadError::Toml(e) => write!(f, "invalid TOML: {}", e),
+            ReloadError::UnknownFormat(path) => {
+                write!(f, "unknown format for {}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReloadError::Io(e) => Some(e),
+            ReloadError::Json(e) => Some(e),
+            ReloadError::Toml(e) => Some(e),
+            ReloadError::UnknownFormat(_) => None,
+        }
+    }
+}
+
+/// A `Singleton` holding the contents of a JSON or TOML file, reloaded on
+/// demand or by a watcher thread.
+///
+/// It dereferences to its singleton, so the value is read with `get` and
+/// friends. A reload that fails leaves the previous value in place, and is
+/// reported through the `log` crate.
+pub struct ReloadableSingleton<T: Sized> {
+    singleton: Singleton<T>,
+    path: PathBuf,
+    format: Format,
+    // The modification time of the file when it was last read.
+    modified: Mutex<Option<SystemTime>>,
+    // Whether the last `reload_if_modified` failed to check the file.
+    check_failed: AtomicBool,
+}
+
+impl<T: DeserializeOwned + Send + Sync + 'static> ReloadableSingleton<T> {
+    /// Loads the file at `path`, in the format named by its extension.
+    pub fn load(path: impl Into<PathBuf>) -> Result<ReloadableSingleton<T>, ReloadError> {
+        let path = path.into();
+        match Format::from_path(&path) {
+            Some(format) => Self::load_with_format(path, format),
+            None => Err(ReloadError::UnknownFormat(path)),
+        }
+    }
+
+    /// Loads the file at `path` in the given format.
+    pub fn load_with_format(
+        path: impl Into<PathBuf>,
+        format: Format,
+    ) -> Result<ReloadableSingleton<T>, ReloadError> {
+        let reloadable = ReloadableSingleton {
+            singleton: Singleton::default(),
+            path: path.into(),
+            format,
+            modified: Mutex::new(None),
+            check_failed: AtomicBool::new(false),
+        };
+        reloadable.read()?;
+        Ok(reloadable)
+    }
+
+    /// Reads the file again and stores its contents. On failure the
+    /// previous value stays and the error is logged as well as returned.
+    pub fn reload(&self) -> Result<(), ReloadError> {
+        self.read().inspect_err(|e| {
+            log::error!("reloading {} failed: {}", self.path.display(), e);
+        })
+    }
+
+    /// Reloads the file if its modification time changed since it was last
+    /// read, and returns whether it did.
+    ///
+    /// A file that failed to load is not read again until it changes, so a
+    /// watcher logs every broken version once. Likewise a file that cannot
+    /// be checked, for instance because it was removed, is logged once until
+    /// a check succeeds again.
+    pub fn reload_if_modified(&self) -> Result<bool, ReloadError> {
+        let modified = fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(ReloadError::Io)
+            .inspect_err(|e| {
+                if !self.check_failed.swap(true, Ordering::Relaxed) {
+                    log::error!("checking {} failed: {}", self.path.display(), e);
+                }
+            })?;
+        self.check_failed.store(false, Ordering::Relaxed);
+        if *self.modified.lock().unwrap_or_else(|e| e.into_inner()) == Some(modified) {
+            return Ok(false);
+        }
+        self.reload().map(|()| true)
+    }
+
+    /// Starts a thread calling `reload_if_modified` every `interval`, until
+    /// the returned watcher is dropped.
+    pub fn watch(self: &Arc<Self>, interval: Duration) -> ReloadWatcher {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread = {
+            let reloadable = self.clone();
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                let _guard = crate::thread::register();
+                let (stopped, wake) = &*stop;
+                let mut stopped = stopped.lock().unwrap_or_else(|e| e.into_inner());
+                while !*stopped {
+                    // Failures are logged by `reload_if_modified`.
+                    let _ = reloadable.reload_if_modified();
+                    stopped = wake
+                        .wait_timeout(stopped, interval)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0;
+                }
+            })
+        };
+        ReloadWatcher {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    // Reads and parses the file, and stores it if it parsed.
+    fn read(&self) -> Result<(), ReloadError> {
+        let mut modified = self.modified.lock().unwrap_or_else(|e| e.into_inner());
+        // Taken before reading, so a write racing with the read is seen by
+        // the next `reload_if_modified`.
+        *modified = fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let text = fs::read_to_string(&self.path).map_err(ReloadError::Io)?;
+        let value = self.format.parse(&text)?;
+        self.singleton.replace(value);
+        Ok(())
+    }
+}
+
+impl<T> ReloadableSingleton<T> {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+}
+
+impl<T> Deref for ReloadableSingleton<T> {
+    type Target = Singleton<T>;
+
+    fn deref(&self) -> &Singleton<T> {
+        &self.singleton
+    }
+}
+
+impl<T> fmt::Debug for ReloadableSingleton<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReloadableSingleton")
+            .field("path", &self.path)
+            .field("format", &self.format)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The thread started by `ReloadableSingleton::watch`. Dropping it stops
+/// the thread and waits for it.
+#[must_use = "the watcher stops when dropped"]
+pub struct ReloadWatcher {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for ReloadWatcher {
+    fn drop(&mut self) {
+        let (stopped, wake) = &*self.stop;
+        *stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl fmt::Debug for ReloadWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReloadWatcher").finish_non_exhaustive()
+    }
+}
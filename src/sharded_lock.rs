@@ -0,0 +1,147 @@
+use crate::local_vec::ThreadLocalVec;
+use std::{
+    cell::UnsafeCell,
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+// A shard per thread number, on its own cache line so that readers on
+// different threads do not contend.
+#[repr(align(128))]
+#[derive(Default)]
+struct Shard(RwLock<()>);
+
+/// A reader-writer lock sharded by thread number.
+///
+/// `read` locks only the shard of the current thread, so readers on
+/// different threads never touch the same cache line. `write` locks every
+/// shard, which makes it more expensive than with a plain `RwLock`. A shard
+/// is added the first time a thread number reads.
+///
+/// Like the registry itself, the lock ignores poisoning.
+pub struct FrcShardedLock<T: ?Sized> {
+    shards: ThreadLocalVec<Shard>,
+    // Held by writers for the whole write, and by readers adding a shard,
+    // so that no shard appears behind the back of a writer.
+    growth: Mutex<()>,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for FrcShardedLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for FrcShardedLock<T> {}
+
+impl<T> FrcShardedLock<T> {
+    pub fn new(value: T) -> FrcShardedLock<T> {
+        FrcShardedLock {
+            shards: ThreadLocalVec::new(),
+            growth: Mutex::new(()),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> FrcShardedLock<T> {
+    /// Locks the shard of the current thread for reading.
+    pub fn read(&self) -> FrcShardedLockReadGuard<'_, T> {
+        let shard = match self.shards.get() {
+            Some(shard) => shard,
+            None => {
+                let _growth = self.growth.lock().unwrap_or_else(|e| e.into_inner());
+                self.shards.get_or(Shard::default)
+            }
+        };
+        FrcShardedLockReadGuard {
+            _shard: shard.0.read().unwrap_or_else(|e| e.into_inner()),
+            value: unsafe { &*self.value.get() },
+        }
+    }
+
+    /// Locks every shard for writing.
+    pub fn write(&self) -> FrcShardedLockWriteGuard<'_, T> {
+        let growth = self.growth.lock().unwrap_or_else(|e| e.into_inner());
+        // Shards are always locked in the same order, so writers cannot
+        // deadlock each other.
+        let shards = self
+            .shards
+            .iter()
+            .map(|shard| shard.0.write().unwrap_or_else(|e| e.into_inner()))
+            .collect();
+        FrcShardedLockWriteGuard {
+            _shards: shards,
+            _growth: growth,
+            value: unsafe { &mut *self.value.get() },
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+impl<T: Default> Default for FrcShardedLock<T> {
+    fn default() -> Self {
+        FrcShardedLock::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for FrcShardedLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FrcShardedLock")
+            .field("value", &&*self.read())
+            .finish()
+    }
+}
+
+/// Keeps the shard of the current thread locked for reading.
+#[must_use = "the lock is released when the guard is dropped"]
+pub struct FrcShardedLockReadGuard<'a, T: ?Sized> {
+    _shard: RwLockReadGuard<'a, ()>,
+    value: &'a T,
+}
+
+impl<T: ?Sized> Deref for FrcShardedLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for FrcShardedLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.value, f)
+    }
+}
+
+/// Keeps every shard locked for writing.
+#[must_use = "the lock is released when the guard is dropped"]
+pub struct FrcShardedLockWriteGuard<'a, T: ?Sized> {
+    _shards: Vec<RwLockWriteGuard<'a, ()>>,
+    _growth: MutexGuard<'a, ()>,
+    value: &'a mut T,
+}
+
+impl<T: ?Sized> Deref for FrcShardedLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T: ?Sized> DerefMut for FrcShardedLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for FrcShardedLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.value, f)
+    }
+}
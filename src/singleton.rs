@@ -1,13 +1,53 @@
+use crate::group::{GroupWrite, SingletonGroup};
 use crate::inner::Inner;
+use crate::policy::{DefaultWeight, PolicyMarker, WeightPolicy};
+use crate::slot::ThreadSlot;
+use crate::sync::{
+    atomic::{self, AtomicPtr, AtomicU64, AtomicUsize},
+    const_unless_loom, Condvar, Mutex,
+};
 use crate::Frc;
 use std::{
+    any::Any,
     cell::Cell,
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    marker::PhantomData,
+    mem,
+    pin::Pin,
     ptr::NonNull,
-    sync::{atomic, atomic::AtomicPtr},
+    sync::OnceLock,
+    task::{Context, Poll, Waker},
+    time::SystemTime,
 };
 
 pub struct Singleton<T: Sized> {
     ptr: AtomicPtr<Inner<T>>,
+    // Creates the value on first `get` of a singleton made by `lazy`.
+    init: Option<fn() -> T>,
+    // Makes `get_or_init` run its initialiser once.
+    init_lock: Mutex<()>,
+    // Bumped on every change of the value.
+    version: AtomicU64,
+    // Tasks waiting in `changed`, and the lock blocking waiters wait on.
+    wakers: Mutex<Vec<Waker>>,
+    change: Condvar,
+    // Handles cached by `load_local`, dropped by their thread when it
+    // unregisters.
+    cache: OnceLock<ThreadSlot<CacheSlot>>,
+    // Readers adding weight without being pinned, which writers wait for
+    // before retiring a value, see `try_get`.
+    unpinned_readers: AtomicUsize,
+    // The last values stored, oldest first, kept for `rollback`. Only locked
+    // when `history_capacity` is not 0.
+    history: Mutex<VecDeque<HistoryEntry<T>>>,
+    history_capacity: usize,
+    // The group whose snapshots see the stores of this singleton.
+    group: Option<&'static SingletonGroup>,
+    // `AtomicPtr` is always `Send + Sync`, the singleton hands out `Frc<T>`
+    // and takes its auto traits from it instead.
+    _marker: PhantomData<Frc<T>>,
 }
 
 impl<T> Singleton<T> {
@@ -16,57 +56,591 @@ impl<T> Singleton<T> {
         // Allocate the ptr on the heap and set the weights of the values
         // to the default.
         let ptr = Box::new(Inner::new(data, 1));
-        Singleton {
-            ptr: AtomicPtr::new(Box::into_raw(ptr)),
+        let singleton = Self::empty(None);
+        singleton
+            .ptr
+            .store(Box::into_raw(ptr), atomic::Ordering::Relaxed);
+        singleton
+    }
+
+    /// Creates a singleton holding `data` that keeps the last `capacity`
+    /// values stored in its history, `data` included.
+    pub fn with_history(data: T, capacity: usize) -> Singleton<T> {
+        let singleton = Self::new(data).keep_history(capacity);
+        let evicted = {
+            let mut history = singleton.history.lock().unwrap_or_else(|e| e.into_inner());
+            singleton.record(
+                &mut history,
+                0,
+                singleton.ptr.load(atomic::Ordering::Relaxed),
+            )
+        };
+        drop(evicted);
+        singleton
+    }
+
+    /// Returns a handle to the current value. A singleton made by `lazy` is
+    /// initialised first if it has no value yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the singleton has no value and no initialiser.
+    #[inline]
+    pub fn get(&self) -> Frc<T> {
+        if let Some(value) = self.try_get() {
+            return value;
+        }
+        match self.init {
+            Some(init) => self.get_or_init(init),
+            None => panic!("Singleton::get called on a singleton without a value"),
         }
     }
 
+    /// Returns a handle to the current value, or `None` if there is none.
+    /// Never runs the initialiser.
     #[inline]
-    pub fn replace(&self, data: T) -> Option<Frc<T>> {
-        let ptr = Box::new(Inner::new(data, 1));
-        let prev_ptr = self.ptr.swap(Box::into_raw(ptr), atomic::Ordering::Relaxed);
-        if !prev_ptr.is_null() {
-            let thread_no = unsafe { *crate::get_thread_number() };
-            Some(Frc {
-                weight: Cell::new(1),
-                ptr: NonNull::new(prev_ptr).unwrap(),
-                thread_no,
-            })
+    pub fn try_get(&self) -> Option<Frc<T>> {
+        // The value cannot lose the weight unit of the singleton while the
+        // thread is pinned, see `replace`.
+        let guard = crate::epoch::try_pin();
+        if guard.is_none() {
+            // The thread locals of this thread are being destroyed, so it
+            // cannot pin. Writers wait for it instead.
+            self.unpinned_readers
+                .fetch_add(1, atomic::Ordering::Relaxed);
+            atomic::fence(atomic::Ordering::SeqCst);
+        }
+        let ptr = NonNull::new(self.ptr.load(atomic::Ordering::Acquire));
+        if let Some(ptr) = ptr {
+            unsafe { ptr.as_ref().add_weight(DefaultWeight::INITIAL_WEIGHT) };
+        }
+        if guard.is_none() {
+            self.unpinned_readers
+                .fetch_sub(1, atomic::Ordering::Release);
+        }
+        let ptr = ptr?;
+        let thread = crate::registry::current_thread();
+
+        Some(Frc {
+            weight: Cell::new(DefaultWeight::INITIAL_WEIGHT),
+            ptr,
+            thread,
+            _policy: PolicyMarker::new(),
+        })
+    }
+
+    /// Returns a handle to the current value like `get`, cloned from a handle
+    /// cached for the current thread.
+    ///
+    /// Clones of the cached handle split its weight locally, so repeated
+    /// reads on a thread leave the shared counter alone. The cache is only
+    /// refreshed when the version of the singleton changes. A cached value
+    /// stays alive until its thread calls `load_local` again or unregisters,
+    /// which an exiting thread does.
+    pub fn load_local(&self) -> Frc<T>
+    where
+        T: 'static,
+    {
+        let current = crate::registry::current_thread();
+        if current.number() == crate::registry::UNREGISTERED {
+            // The thread locals are being destroyed, there is nowhere to
+            // cache the handle.
+            return self.get();
+        }
+        let cache = self.cache.get_or_init(ThreadSlot::new);
+        let version = self.version();
+        let cached = cache.with(|slot| {
+            let slot = slot.filter(|slot| slot.version == version)?;
+            let cached = slot.frc.downcast_ref::<Frc<T>>()?;
+            // A handle from before the thread registered again would clone
+            // through the shared counter.
+            cached.thread.is_current(current).then(|| cached.clone())
+        });
+        if let Some(cached) = cached {
+            return cached;
+        }
+        let frc = self.get();
+        cache.set(CacheSlot {
+            version,
+            frc: Box::new(frc.clone()),
+        });
+        frc
+    }
+
+    /// Returns a handle to the current value, storing the result of `f`
+    /// first if there is none.
+    ///
+    /// Racing callers run at most one initialiser; the others wait for it
+    /// and share its value.
+    pub fn get_or_init(&self, f: impl FnOnce() -> T) -> Frc<T> {
+        if let Some(value) = self.try_get() {
+            return value;
+        }
+        {
+            let _init = self.init_lock.lock().unwrap_or_else(|e| e.into_inner());
+            if self.ptr.load(atomic::Ordering::Acquire).is_null() {
+                let ptr = Box::into_raw(Box::new(Inner::new(f(), 1)));
+                let _write = self.group_write();
+                // A racing `replace` may have stored a value meanwhile, which
+                // then wins.
+                if self.store(ptr, Some(std::ptr::null_mut())).is_err() {
+                    release_unit(unsafe { NonNull::new_unchecked(ptr) });
+                }
+            }
+        }
+        self.get()
+    }
+
+    const_unless_loom! {
+        pub fn default() -> Self {
+            Self::empty(None)
+        }
+    }
+
+    const_unless_loom! {
+        /// Creates a singleton whose value is created by `init` on first `get`.
+        pub fn lazy(init: fn() -> T) -> Self {
+            Self::empty(Some(init))
+        }
+    }
+
+    /// Makes the singleton keep the last `capacity` values stored from now
+    /// on in its history, for `rollback`. A value given to `new` is not
+    /// recorded, see `with_history` for that.
+    pub const fn keep_history(mut self, capacity: usize) -> Self {
+        self.history_capacity = capacity;
+        self
+    }
+
+    /// Makes the singleton a member of `group`: every store into it is then
+    /// seen by `SingletonGroup::snapshot` as a whole, and transactions of
+    /// the group may update it.
+    pub const fn in_group(mut self, group: &'static SingletonGroup) -> Self {
+        self.group = Some(group);
+        self
+    }
+
+    // The write of the group of the singleton, if any, held across a store
+    // so that snapshots retry around it.
+    fn group_write(&self) -> Option<GroupWrite<'static>> {
+        self.group.map(SingletonGroup::write)
+    }
+
+    pub(crate) fn is_in_group(&self, group: &SingletonGroup) -> bool {
+        self.group
+            .is_some_and(|member_of| std::ptr::eq(member_of, group))
+    }
+
+    const_unless_loom! {
+        fn empty(init: Option<fn() -> T>) -> Self {
+            Singleton {
+                ptr: AtomicPtr::new(std::ptr::null_mut()),
+                init,
+                init_lock: Mutex::new(()),
+                version: AtomicU64::new(0),
+                wakers: Mutex::new(Vec::new()),
+                change: Condvar::new(),
+                cache: OnceLock::new(),
+                unpinned_readers: AtomicUsize::new(0),
+                history: Mutex::new(VecDeque::new()),
+                history_capacity: 0,
+                group: None,
+                _marker: PhantomData,
+            }
+        }
+    }
+
+    /// The number of times the value has changed. Every `replace`, and every
+    /// other call storing or removing a value, adds one.
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.version.load(atomic::Ordering::Acquire)
+    }
+
+    /// Blocks until the version differs from `last_version`, and returns the
+    /// new version.
+    pub fn wait_for_change(&self, last_version: u64) -> u64 {
+        let mut wakers = self.wakers.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            let version = self.version();
+            if version != last_version {
+                return version;
+            }
+            wakers = self.change.wait(wakers).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Returns a future resolving to the new version once the value changes
+    /// after this call. It needs no particular runtime.
+    pub fn changed(&self) -> Changed<'_, T> {
+        Changed {
+            singleton: self,
+            last_version: self.version(),
+        }
+    }
+
+    /// The values recorded in the history, oldest first. The last one is the
+    /// current value, unless the singleton was emptied since.
+    pub fn history(&self) -> Vec<HistoryEntry<T>> {
+        let history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        history.iter().cloned().collect()
+    }
+
+    // Stores `new`, null emptying the singleton, if the singleton holds
+    // `expected`, or whatever it holds when `expected` is `None`. Returns the
+    // pointer it replaced, or the one it found instead of `expected`.
+    fn store(
+        &self,
+        new: *mut Inner<T>,
+        expected: Option<*mut Inner<T>>,
+    ) -> Result<*mut Inner<T>, *mut Inner<T>> {
+        // Held across the store, so the history follows the order of the
+        // versions.
+        let mut history = (self.history_capacity > 0)
+            .then(|| self.history.lock().unwrap_or_else(|e| e.into_inner()));
+        // Release publishes the new value to `get`, Acquire pairs with the
+        // publication of the previous one.
+        let prev = match expected {
+            None => self.ptr.swap(new, atomic::Ordering::AcqRel),
+            Some(expected) => self.ptr.compare_exchange(
+                expected,
+                new,
+                atomic::Ordering::AcqRel,
+                atomic::Ordering::Acquire,
+            )?,
+        };
+        self.wait_for_unpinned_readers();
+        let version = self.version.fetch_add(1, atomic::Ordering::Release) + 1;
+        let evicted = history
+            .as_mut()
+            .and_then(|history| self.record(history, version, new));
+        drop(history);
+        drop(evicted);
+        self.notify();
+        Ok(prev)
+    }
+
+    // Waits for the readers that could not pin and may have loaded the value
+    // just unlinked, called right after unlinking it. A reader announcing
+    // itself after the fence loads the new value.
+    fn wait_for_unpinned_readers(&self) {
+        atomic::fence(atomic::Ordering::SeqCst);
+        while self.unpinned_readers.load(atomic::Ordering::Acquire) != 0 {
+            crate::sync::yield_now();
+        }
+    }
+
+    // Appends the value behind `ptr` to the history, and returns the entry
+    // that no longer fits. The singleton keeps the value alive meanwhile.
+    fn record(
+        &self,
+        history: &mut VecDeque<HistoryEntry<T>>,
+        version: u64,
+        ptr: *mut Inner<T>,
+    ) -> Option<HistoryEntry<T>> {
+        let ptr = NonNull::new(ptr)?;
+        unsafe { ptr.as_ref().add_weight(DefaultWeight::INITIAL_WEIGHT) };
+        history.push_back(HistoryEntry {
+            version,
+            stored_at: SystemTime::now(),
+            value: Frc {
+                weight: Cell::new(DefaultWeight::INITIAL_WEIGHT),
+                ptr,
+                thread: crate::registry::current_thread(),
+                _policy: PolicyMarker::new(),
+            },
+        });
+        if history.len() > self.history_capacity {
+            history.pop_front()
         } else {
             None
         }
     }
 
+    // Wakes everything waiting for a change, after the version was bumped.
+    fn notify(&self) {
+        // Taking the lock orders the bump against waiters that saw the old
+        // version and are about to wait.
+        let wakers = {
+            let mut wakers = self.wakers.lock().unwrap_or_else(|e| e.into_inner());
+            self.change.notify_all();
+            mem::take(&mut *wakers)
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+// The handle cached by `load_local` on one thread, an `Frc<T>`, and the
+// version it was loaded at. Erased, so that `Singleton<T>` needs no
+// `T: 'static`.
+struct CacheSlot {
+    version: u64,
+    frc: Box<dyn Any>,
+}
+
+/// A value recorded in the history of a singleton.
+pub struct HistoryEntry<T> {
+    version: u64,
+    stored_at: SystemTime,
+    value: Frc<T>,
+}
+
+impl<T> HistoryEntry<T> {
+    /// The version of the singleton right after the value was stored.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// When the value was stored.
+    pub fn stored_at(&self) -> SystemTime {
+        self.stored_at
+    }
+
+    pub fn value(&self) -> &Frc<T> {
+        &self.value
+    }
+}
+
+impl<T> Clone for HistoryEntry<T> {
+    fn clone(&self) -> Self {
+        HistoryEntry {
+            version: self.version,
+            stored_at: self.stored_at,
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for HistoryEntry<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HistoryEntry")
+            .field("version", &self.version)
+            .field("stored_at", &self.stored_at)
+            .field("value", &&*self.value)
+            .finish()
+    }
+}
+
+/// The error returned by `Singleton::rollback` and `Singleton::rollback_to`
+/// when the history holds no value to go back to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NotInHistory;
+
+impl fmt::Display for NotInHistory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no such value in the singleton history")
+    }
+}
+
+impl std::error::Error for NotInHistory {}
+
+/// The future returned by `Singleton::changed`.
+#[must_use = "futures do nothing unless polled"]
+pub struct Changed<'a, T> {
+    singleton: &'a Singleton<T>,
+    last_version: u64,
+}
+
+impl<T> Future for Changed<'_, T> {
+    type Output = u64;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u64> {
+        let version = self.singleton.version();
+        if version != self.last_version {
+            return Poll::Ready(version);
+        }
+        let mut wakers = self
+            .singleton
+            .wakers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        // Checked again under the lock, so a change cannot slip in before
+        // the waker is stored.
+        let version = self.singleton.version();
+        if version != self.last_version {
+            return Poll::Ready(version);
+        }
+        if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl<T> fmt::Debug for Changed<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Changed")
+            .field("last_version", &self.last_version)
+            .finish()
+    }
+}
+
+impl<T: Send + Sync + 'static> Singleton<T> {
+    /// Stores `data` as the new value and returns a handle to the previous
+    /// one.
+    ///
+    /// A `get` racing with `replace` may still be adding weight to the
+    /// previous value, so the singleton keeps its weight unit on it until
+    /// every thread pinned at this point has unpinned. Readers that cannot
+    /// pin, in a thread local destructor, are waited for instead.
     #[inline]
-    pub fn get(&self) -> Frc<T> {
-        // Get from inner directly
-        let ptr = self.ptr.load(atomic::Ordering::Relaxed);
-        unsafe {
-            ptr.as_ref().unwrap().add_weight(crate::imp::DEFAULT_WEIGHT);
+    pub fn replace(&self, data: T) -> Option<Frc<T>> {
+        let _write = self.group_write();
+        self.swap_in_group(Some(data))
+    }
+
+    /// Stores `data` only if the singleton still holds the value `current`
+    /// points to, compared by pointer identity. Returns a handle to the
+    /// previous value, or gives `data` back if another value was stored
+    /// meanwhile.
+    pub fn compare_and_swap(&self, current: &Frc<T>, data: T) -> Result<Frc<T>, T> {
+        let ptr = Box::into_raw(Box::new(Inner::new(data, 1)));
+        let _write = self.group_write();
+        // `current` keeps its value alive, so its address cannot be reused
+        // by another value while comparing.
+        match self.store(ptr, Some(current.ptr.as_ptr())) {
+            Ok(prev_ptr) => Ok(retire(unsafe { NonNull::new_unchecked(prev_ptr) })),
+            // Never published, so nobody else can see it.
+            Err(_) => Err(unsafe { Box::from_raw(ptr) }.data),
         }
-        let thread_no = unsafe { *crate::get_thread_number() };
+    }
 
-        Frc {
-            weight: Cell::new(crate::imp::DEFAULT_WEIGHT),
-            ptr: NonNull::new(ptr).unwrap(),
-            thread_no,
+    /// Replaces the value with `f(&value)` until no other update comes in
+    /// between, and returns a handle to the value it replaced.
+    ///
+    /// `f` may run several times when updates race. A singleton made by
+    /// `lazy` is initialised first, like `get` does. If the singleton is
+    /// empty, or emptied by a racing `take`, nothing is stored and `None` is
+    /// returned.
+    pub fn rcu(&self, mut f: impl FnMut(&T) -> T) -> Option<Frc<T>> {
+        let mut current = self
+            .try_get()
+            .or_else(|| self.init.map(|init| self.get_or_init(init)))?;
+        loop {
+            match self.compare_and_swap(&current, f(&current)) {
+                Ok(prev) => return Some(prev),
+                Err(_) => current = self.try_get()?,
+            }
         }
     }
-    pub const fn default() -> Self {
-        Singleton {
-            ptr: AtomicPtr::new(std::ptr::null_mut()),
+
+    /// Empties the singleton and returns a handle to the value it held.
+    pub fn take(&self) -> Option<Frc<T>> {
+        let _write = self.group_write();
+        self.swap_in_group(None)
+    }
+
+    // Stores `data`, or empties the singleton, for a caller that already
+    // holds the write of the group if there is one.
+    pub(crate) fn swap_in_group(&self, data: Option<T>) -> Option<Frc<T>> {
+        let ptr = data.map_or(std::ptr::null_mut(), |data| {
+            Box::into_raw(Box::new(Inner::new(data, 1)))
+        });
+        // Never fails without an expected value.
+        let (Ok(prev_ptr) | Err(prev_ptr)) = self.store(ptr, None);
+        Some(retire(NonNull::new(prev_ptr)?))
+    }
+
+    /// Empties the singleton, dropping its handle to the value it held.
+    pub fn clear(&self) {
+        drop(self.take());
+    }
+
+    /// Stores again the last value of the history that is not the current
+    /// one, and returns a handle to it. The entries recorded after it are
+    /// dropped from the history.
+    pub fn rollback(&self) -> Result<Frc<T>, NotInHistory> {
+        self.restore(|history, current| {
+            let last = history.len().checked_sub(1)?;
+            if history[last].value.ptr.as_ptr() == current {
+                last.checked_sub(1)
+            } else {
+                Some(last)
+            }
+        })
+    }
+
+    /// Stores again the value recorded in the history as `version`, and
+    /// returns a handle to it. The entries recorded after it are dropped
+    /// from the history.
+    pub fn rollback_to(&self, version: u64) -> Result<Frc<T>, NotInHistory> {
+        self.restore(|history, _| history.iter().position(|entry| entry.version == version))
+    }
+
+    // Stores the history entry picked by `pick` from the history and the
+    // current pointer. The entry keeps its version, the singleton gets a new
+    // one all the same.
+    fn restore(
+        &self,
+        pick: impl FnOnce(&VecDeque<HistoryEntry<T>>, *mut Inner<T>) -> Option<usize>,
+    ) -> Result<Frc<T>, NotInHistory> {
+        let write = self.group_write();
+        let (value, prev_ptr, dropped) = {
+            // Stores wait for the lock as long as there is a history.
+            let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+            let index =
+                pick(&history, self.ptr.load(atomic::Ordering::Acquire)).ok_or(NotInHistory)?;
+            let value = history[index].value.clone();
+            // The weight unit of the singleton.
+            unsafe { value.ptr.as_ref().add_weight(1) };
+            let prev_ptr = self.ptr.swap(value.ptr.as_ptr(), atomic::Ordering::AcqRel);
+            self.wait_for_unpinned_readers();
+            self.version.fetch_add(1, atomic::Ordering::Release);
+            (value, prev_ptr, history.split_off(index + 1))
+        };
+        drop(write);
+        drop(dropped);
+        self.notify();
+        if let Some(prev_ptr) = NonNull::new(prev_ptr) {
+            drop(retire(prev_ptr));
         }
+        Ok(value)
     }
 }
 
+// Hands out a handle to a value just unlinked from a singleton, and releases
+// the weight unit of the singleton once no `get` can still be adding weight
+// to it.
+fn retire<T: Send + Sync + 'static>(ptr: NonNull<Inner<T>>) -> Frc<T> {
+    unsafe { ptr.as_ref().add_weight(DefaultWeight::INITIAL_WEIGHT) };
+    let prev = Frc {
+        weight: Cell::new(DefaultWeight::INITIAL_WEIGHT),
+        ptr,
+        thread: crate::registry::current_thread(),
+        _policy: PolicyMarker::new(),
+    };
+
+    struct Unit<T>(NonNull<Inner<T>>);
+    // The unit is released like an `Frc<T>`.
+    unsafe impl<T: Send + Sync> Send for Unit<T> {}
+    let unit = Unit(ptr);
+    // Writers need not be pinned, so they also work in thread local
+    // destructors.
+    crate::epoch::defer_unpinned(move || {
+        let unit = unit;
+        release_unit(unit.0);
+    });
+    prev
+}
+
 impl<T: Sized> Drop for Singleton<T> {
     fn drop(&mut self) {
-        let ptr = self.ptr.load(atomic::Ordering::Relaxed);
-        if !ptr.is_null() {
-            let data = unsafe { Box::from_raw(ptr) };
-            if data.drop_weight(1) == 0 && data.get_weight(atomic::Ordering::Acquire) == 0 {
-                drop(data);
-            }
+        let ptr = self.ptr.load(atomic::Ordering::Acquire);
+        // No `get` can be running, so the unit is released right away.
+        if let Some(ptr) = NonNull::new(ptr) {
+            release_unit(ptr);
         }
     }
 }
+
+// The singleton owns a weight of 1, release it like any handle.
+fn release_unit<T>(ptr: NonNull<Inner<T>>) {
+    drop(Frc::<T> {
+        weight: Cell::new(1),
+        ptr,
+        thread: crate::registry::current_thread(),
+        _policy: PolicyMarker::new(),
+    });
+}
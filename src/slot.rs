@@ -0,0 +1,156 @@
+// Thread local slots keyed at runtime. Every thread keeps its values in a
+// vector indexed by the slot key, and drops them when it unregisters.
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::BTreeSet,
+    fmt,
+    marker::PhantomData,
+    mem,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+// Slot keys released by dropped slots, reused smallest first, and the next
+// key to hand out when there are none.
+struct Keys {
+    free: BTreeSet<usize>,
+    next: usize,
+}
+
+static KEYS: Mutex<Keys> = Mutex::new(Keys {
+    free: BTreeSet::new(),
+    next: 0,
+});
+// Tells a slot apart from the previous holders of its key.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+struct Entry {
+    generation: u64,
+    // Shared with running `ThreadSlot::with` calls, so that replacing the
+    // value from inside one cannot free it under the caller.
+    value: Rc<dyn Any>,
+}
+
+thread_local! {
+    static SLOTS: RefCell<Vec<Option<Entry>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A thread local value whose key is allocated at runtime.
+///
+/// Every thread sees its own value. Values are dropped when their thread
+/// unregisters, or exits, whichever comes first. Dropping the slot drops the
+/// value of the current thread; values left on other threads are dropped
+/// when those threads unregister.
+pub struct ThreadSlot<T: 'static> {
+    key: usize,
+    generation: u64,
+    // Values never leave their thread, so the slot is `Send + Sync` for any
+    // `T`, like `LocalKey`.
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: 'static> ThreadSlot<T> {
+    /// Allocates a new slot key.
+    pub fn new() -> ThreadSlot<T> {
+        let key = {
+            let mut keys = KEYS.lock().unwrap_or_else(|e| e.into_inner());
+            keys.free.pop_first().unwrap_or_else(|| {
+                keys.next += 1;
+                keys.next - 1
+            })
+        };
+        ThreadSlot {
+            key,
+            generation: GENERATION.fetch_add(1, Ordering::Relaxed),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Stores `value` for the current thread, registering the thread if it
+    /// is not registered yet. The previous value is dropped.
+    pub fn set(&self, value: T) {
+        crate::registry::current_thread();
+        let entry = Entry {
+            generation: self.generation,
+            value: Rc::new(value),
+        };
+        let prev = SLOTS.with(|slots| {
+            let mut slots = slots.borrow_mut();
+            if slots.len() <= self.key {
+                slots.resize_with(self.key + 1, || None);
+            }
+            slots[self.key].replace(entry)
+        });
+        drop(prev);
+    }
+
+    /// Drops the value of the current thread, if any.
+    pub fn clear(&self) {
+        let prev = SLOTS.with(|slots| self.take_entry(&mut slots.borrow_mut()));
+        drop(prev);
+    }
+
+    /// Calls `f` with the value of the current thread, or `None` if it has
+    /// not been set.
+    pub fn with<R>(&self, f: impl FnOnce(Option<&T>) -> R) -> R {
+        let value = SLOTS.with(|slots| match slots.borrow().get(self.key) {
+            Some(Some(entry)) if entry.generation == self.generation => Some(entry.value.clone()),
+            _ => None,
+        });
+        f(value.as_deref().and_then(|v| v.downcast_ref()))
+    }
+
+    /// Whether the current thread has a value in this slot.
+    pub fn is_set(&self) -> bool {
+        self.with(|value| value.is_some())
+    }
+
+    fn take_entry(&self, slots: &mut [Option<Entry>]) -> Option<Entry> {
+        match slots.get_mut(self.key) {
+            Some(slot)
+                if slot
+                    .as_ref()
+                    .is_some_and(|e| e.generation == self.generation) =>
+            {
+                slot.take()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<T: 'static> Default for ThreadSlot<T> {
+    fn default() -> Self {
+        ThreadSlot::new()
+    }
+}
+
+impl<T: 'static> Drop for ThreadSlot<T> {
+    fn drop(&mut self) {
+        let prev = SLOTS.try_with(|slots| self.take_entry(&mut slots.borrow_mut()));
+        drop(prev);
+        KEYS.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .free
+            .insert(self.key);
+    }
+}
+
+impl<T: 'static> fmt::Debug for ThreadSlot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ThreadSlot")
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+// Drops every value of the current thread. Values set by the destructors
+// are kept until the next release.
+pub(crate) fn release_thread() {
+    let values = SLOTS.try_with(|slots| mem::take(&mut *slots.borrow_mut()));
+    drop(values);
+}
@@ -0,0 +1,34 @@
+// Atomics used by the weight counters and `Singleton`, the locks guarding
+// its initialisation, change notification and `SingletonGroup` writes, and
+// the yield of writers waiting for unpinned readers. A `cfg(loom)` build
+// swaps in the loom types so weight transfers can be model checked.
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic;
+#[cfg(not(loom))]
+pub(crate) use std::sync::atomic;
+
+#[cfg(loom)]
+pub(crate) use loom::sync::{Condvar, Mutex, MutexGuard};
+#[cfg(not(loom))]
+pub(crate) use std::sync::{Condvar, Mutex, MutexGuard};
+
+#[cfg(loom)]
+pub(crate) use loom::thread::yield_now;
+#[cfg(not(loom))]
+pub(crate) use std::thread::yield_now;
+
+// Defines a `const fn`, or a plain `fn` under loom, whose atomics cannot be
+// created in a const context. Both come from the same body, so they cannot
+// drift apart.
+macro_rules! const_unless_loom {
+    ($(#[$attr:meta])* $vis:vis fn $name:ident($($args:tt)*) -> $ret:ty $body:block) => {
+        $(#[$attr])*
+        #[cfg(not(loom))]
+        $vis const fn $name($($args)*) -> $ret $body
+
+        $(#[$attr])*
+        #[cfg(loom)]
+        $vis fn $name($($args)*) -> $ret $body
+    };
+}
+pub(crate) use const_unless_loom;
@@ -0,0 +1,83 @@
+//! Safe registration of threads with the thread registry.
+//!
+//! `Frc` tells apart the threads it is cloned on by their thread number, so
+//! every thread using it should hold one for as long as it does.
+use crate::registry::{Backend, Registry};
+use std::{error::Error, fmt, marker::PhantomData};
+
+pub use crate::local_vec::ThreadLocalVec;
+pub use crate::slot::ThreadSlot;
+
+/// Keeps the current thread registered, releasing its thread number for
+/// reuse when dropped.
+#[must_use = "the thread is unregistered again when the guard is dropped"]
+pub struct ThreadGuard {
+    // Whether this guard made the registration, and so has to undo it.
+    release: bool,
+    // The registration belongs to the thread that made it.
+    _not_send: PhantomData<*const ()>,
+}
+
+/// Registers the current thread, giving it the smallest free thread number.
+///
+/// If the thread was registered on first use, or unregistered while pinned
+/// by `frc::epoch::pin`, the guard takes over releasing it. If it is
+/// registered by another guard, the returned guard leaves that registration
+/// alone when dropped.
+pub fn register() -> ThreadGuard {
+    // A registration made on first use is taken over by the guard.
+    let release = unsafe { Backend::initialize_thread_number() }
+        || crate::registry::adopt_lazy_registration()
+        || crate::epoch::take_deferred_unregister();
+    ThreadGuard {
+        release,
+        _not_send: PhantomData,
+    }
+}
+
+/// The thread number of the current thread, or `None` if it is not
+/// registered.
+pub fn current_thread_number() -> Option<u32> {
+    if Backend::is_thread_registered() {
+        Some(unsafe { *Backend::get_thread_number() })
+    } else {
+        None
+    }
+}
+
+impl Drop for ThreadGuard {
+    fn drop(&mut self) {
+        if self.release {
+            crate::registry::unregister();
+        }
+    }
+}
+
+impl fmt::Debug for ThreadGuard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ThreadGuard")
+            .field("thread_number", &current_thread_number())
+            .finish()
+    }
+}
+
+/// The error returned by the raw registry calls when a registration is made
+/// or released twice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegistrationError {
+    /// The thread already holds a thread number.
+    AlreadyRegistered,
+    /// The thread holds no thread number.
+    NotRegistered,
+}
+
+impl fmt::Display for RegistrationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RegistrationError::AlreadyRegistered => write!(f, "thread is already registered"),
+            RegistrationError::NotRegistered => write!(f, "thread is not registered"),
+        }
+    }
+}
+
+impl Error for RegistrationError {}
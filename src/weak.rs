@@ -0,0 +1,85 @@
+use crate::imp::split_weight;
+use crate::inner::Inner;
+use crate::policy::{DefaultWeight, PolicyMarker, WeightPolicy};
+use crate::registry::ThreadToken;
+use crate::sync::atomic;
+use crate::Frc;
+use std::{alloc::Layout, cell::Cell, convert::Infallible, fmt, ptr::NonNull};
+
+/// A weighted weak reference to an `Frc` allocation.
+///
+/// Weak handles carry their own weight on the weak counter of `Inner`, so
+/// same-thread clones split that weight locally just like `Frc` does.
+pub struct FrcWeak<T: ?Sized, P: WeightPolicy = DefaultWeight> {
+    pub(crate) weight: Cell<usize>,
+    pub(crate) ptr: NonNull<Inner<T>>,
+    pub(crate) thread: ThreadToken,
+    pub(crate) _policy: PolicyMarker<P>,
+}
+
+impl<T: ?Sized, P: WeightPolicy> FrcWeak<T, P> {
+    /// Attempts to upgrade to an `Frc`, returning `None` if the data has
+    /// already been dropped.
+    pub fn upgrade(&self) -> Option<Frc<T, P>> {
+        if !self.inner().add_weight_if_alive(P::INITIAL_WEIGHT) {
+            return None;
+        }
+        Some(Frc {
+            weight: Cell::new(P::INITIAL_WEIGHT),
+            ptr: self.ptr,
+            thread: crate::registry::current_thread(),
+            _policy: PolicyMarker::new(),
+        })
+    }
+
+    #[inline]
+    fn inner(&self) -> &Inner<T> {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+unsafe impl<T: ?Sized + Send + Sync, P: WeightPolicy> Send for FrcWeak<T, P> {}
+unsafe impl<T: ?Sized + Send + Sync, P: WeightPolicy> Sync for FrcWeak<T, P> {}
+
+impl<T: ?Sized, P: WeightPolicy> Clone for FrcWeak<T, P> {
+    fn clone(&self) -> Self {
+        let current = crate::registry::current_thread();
+        let new_weight = if self.thread.is_current(current) {
+            let Ok(new_weight) = split_weight::<P, _>(&self.weight, |add_wei| {
+                self.inner().add_weak_weight(add_wei);
+                Ok::<(), Infallible>(())
+            });
+            new_weight
+        } else {
+            self.inner().add_weak_weight(P::INITIAL_WEIGHT);
+            P::INITIAL_WEIGHT
+        };
+        FrcWeak {
+            weight: Cell::new(new_weight),
+            ptr: self.ptr,
+            thread: current,
+            _policy: PolicyMarker::new(),
+        }
+    }
+}
+
+impl<T: ?Sized, P: WeightPolicy> Drop for FrcWeak<T, P> {
+    fn drop(&mut self) {
+        let inner = unsafe { self.ptr.as_ref() };
+        if inner.drop_weak_weight(self.weight.get()) > 0 {
+            return;
+        }
+        atomic::fence(atomic::Ordering::Acquire);
+
+        // The data was already dropped by the last strong handle, only the
+        // allocation is left.
+        let layout = Layout::for_value(inner);
+        unsafe { std::alloc::dealloc(self.ptr.as_ptr() as *mut u8, layout) };
+    }
+}
+
+impl<T: ?Sized, P: WeightPolicy> fmt::Debug for FrcWeak<T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(FrcWeak)")
+    }
+}
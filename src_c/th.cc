@@ -2,44 +2,62 @@
 #include <atomic>
 #include <set>
 #include <mutex>
+#include <cstdint>
 #include "th.h"
 
 #define TLOCAL_ARRAY_LEN 1024
+// Read by unregistered threads, never handed out.
+#define UNREGISTERED UINT32_MAX
 
 static std::atomic<uint32_t> TINC(0);
+static std::atomic<uint32_t> TGEN(0);
 static std::set<uint32_t> TSET;
 static std::mutex T_MUTEX;
 thread_local bool is_initialized = false;
-thread_local uint32_t TNUM = 0;
+thread_local uint32_t TNUM = UNREGISTERED;
+thread_local uint32_t GEN = 0;
 thread_local void *TPTR_ARRAY[TLOCAL_ARRAY_LEN];
 
-void initialize_thread_number()
+bool initialize_thread_number()
 {
-    if (!is_initialized)
+    if (is_initialized)
     {
+        return false;
+    }
+    {
+        std::lock_guard<std::mutex> guard(T_MUTEX);
+        if (TSET.size() > 0)
         {
-            std::lock_guard<std::mutex> guard(T_MUTEX);
-            if (TSET.size() > 0)
-            {
-                auto begin = TSET.begin();
-                TNUM = *begin;
-                TSET.erase(TNUM);
-                return;
-            }
+            auto begin = TSET.begin();
+            TNUM = *begin;
+            TSET.erase(begin);
+        }
+        else
+        {
+            TNUM = TINC.fetch_add(1, std::memory_order::memory_order_relaxed);
         }
-        auto tnum = TINC.fetch_add(1, std::memory_order::memory_order_relaxed);
-        TNUM = tnum;
-        is_initialized = true;
     }
+    GEN = TGEN.fetch_add(1, std::memory_order::memory_order_relaxed);
+    is_initialized = true;
+    return true;
 }
 
-void uninitialize_thread_number()
+bool uninitialize_thread_number()
 {
-    if (is_initialized)
+    if (!is_initialized)
     {
-        std::lock_guard<std::mutex> guard(T_MUTEX);
-        TSET.insert(TNUM);
+        return false;
     }
+    std::lock_guard<std::mutex> guard(T_MUTEX);
+    TSET.insert(TNUM);
+    TNUM = UNREGISTERED;
+    is_initialized = false;
+    return true;
+}
+
+bool is_thread_registered()
+{
+    return is_initialized;
 }
 
 unsigned int *get_thread_number()
@@ -47,6 +65,11 @@ unsigned int *get_thread_number()
     return &TNUM;
 }
 
+unsigned int get_thread_generation()
+{
+    return GEN;
+}
+
 void store_local_pointer(int pos, void *ptr)
 {
     TPTR_ARRAY[pos] = ptr;
@@ -55,4 +78,4 @@ void store_local_pointer(int pos, void *ptr)
 void *get_local_pointer(int pos)
 {
     return TPTR_ARRAY[pos];
-}
\ No newline at end of file
+}
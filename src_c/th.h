@@ -4,9 +4,11 @@
 extern "C"
 {
 #endif
-    extern void initialize_thread_number();
-    extern void uninitialize_thread_number();
+    extern bool initialize_thread_number();
+    extern bool uninitialize_thread_number();
     extern unsigned int *get_thread_number();
+    extern unsigned int get_thread_generation();
+    extern bool is_thread_registered();
     extern void store_local_pointer(int pos, void *ptr);
     extern void *get_local_pointer(int pos);
 #ifdef __cplusplus